@@ -1,3 +1,6 @@
 pub mod app;
 pub mod pcap;
-pub mod network;
\ No newline at end of file
+pub mod pcapng;
+pub mod network;
+#[cfg(test)]
+mod fixtures;
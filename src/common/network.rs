@@ -4,15 +4,22 @@ use std::error::Error;
 use std::fmt::{Debug, Display, Formatter};
 
 pub mod link;
+pub mod datalink;
 pub mod ethernet2;
+pub mod llc;
+pub mod loopback;
+pub mod mpls;
 pub mod packet;
+pub mod sll;
 
 #[derive(Debug)]
 pub enum ReadError {
     IPUnexpectedVersion(u8),
     DataOffsetTooSmall(usize),
     CouldntParse,
-    UnsupportedIpExtension
+    UnsupportedIpExtension,
+    UnsupportedLinkType(u16),
+    UnsupportedEtherType(u16)
 }
 
 impl Display for ReadError {
@@ -30,6 +37,25 @@ impl Display for ReadError {
             ReadError::UnsupportedIpExtension => {
                 write!(f, "Passed Ip extension number is not supported.")
             }
+            ReadError::UnsupportedLinkType(n) => {
+                write!(f, "Link type {} is not supported.", n)
+            }
+            ReadError::UnsupportedEtherType(n) => {
+                write!(f, "Ethertype {:#06x} is not supported.", n)
+            }
+        }
+    }
+}
+
+impl ReadError {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ReadError::IPUnexpectedVersion(_) => "IPUnexpectedVersion",
+            ReadError::DataOffsetTooSmall(_) => "DataOffsetTooSmall",
+            ReadError::CouldntParse => "CouldntParse",
+            ReadError::UnsupportedIpExtension => "UnsupportedIpExtension",
+            ReadError::UnsupportedLinkType(_) => "UnsupportedLinkType",
+            ReadError::UnsupportedEtherType(_) => "UnsupportedEtherType"
         }
     }
 }
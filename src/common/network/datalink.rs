@@ -0,0 +1,125 @@
+use serde::Serialize;
+use crate::common::network::ethernet2::{Ethernet2Header, VlanTag, MAX_802_3_LENGTH};
+use crate::common::network::llc::SAP_SNAP;
+use crate::common::network::loopback::NullHeader;
+use crate::common::network::mpls::MplsLabel;
+use crate::common::network::packet::PacketReader;
+use crate::common::network::sll::{Sll2Header, SllHeader};
+use crate::common::network::ReadError;
+
+pub const LINKTYPE_NULL: u16 = 0;
+pub const LINKTYPE_ETHERNET: u16 = 1;
+// Live captures report DLT_RAW, which is 12 on most platforms and 14 on OpenBSD.
+pub const DLT_RAW: u16 = 12;
+pub const DLT_RAW_OPENBSD: u16 = 14;
+pub const LINKTYPE_RAW: u16 = 101;
+pub const LINKTYPE_LOOP: u16 = 108;
+pub const LINKTYPE_LINUX_SLL: u16 = 113;
+pub const LINKTYPE_LINUX_SLL2: u16 = 276;
+
+pub const ETHERTYPE_IPV4: u16 = 0x0800;
+pub const ETHERTYPE_IPV6: u16 = 0x86DD;
+
+#[derive(Serialize)]
+pub enum LinkHeader {
+    Ethernet2(Ethernet2Header),
+    LinuxSll(SllHeader),
+    LinuxSll2(Sll2Header),
+    Null(NullHeader),
+    Loop(NullHeader),
+    // Raw IP has no link header at all.
+    Raw
+}
+
+impl LinkHeader {
+    pub fn new<'a, 'b: 'a>(link_type: u16, packet_reader: &'a mut PacketReader<'b>) -> Result<LinkHeader, ReadError> {
+        match link_type {
+            LINKTYPE_ETHERNET => Ok(LinkHeader::Ethernet2(Ethernet2Header::new(packet_reader)?)),
+            LINKTYPE_LINUX_SLL => Ok(LinkHeader::LinuxSll(SllHeader::new(packet_reader)?)),
+            LINKTYPE_LINUX_SLL2 => Ok(LinkHeader::LinuxSll2(Sll2Header::new(packet_reader)?)),
+            LINKTYPE_NULL => Ok(LinkHeader::Null(NullHeader::new_host_order(packet_reader)?)),
+            LINKTYPE_LOOP => Ok(LinkHeader::Loop(NullHeader::new_network_order(packet_reader)?)),
+            LINKTYPE_RAW | DLT_RAW | DLT_RAW_OPENBSD => Ok(LinkHeader::Raw),
+            link_type => Err(ReadError::UnsupportedLinkType(link_type))
+        }
+    }
+
+    // The ethertype of the payload, when the link header names it.
+    pub fn ether_type(&self) -> Option<u16> {
+        match self {
+            LinkHeader::Ethernet2(header) => header.payload_type(),
+            LinkHeader::LinuxSll(header) => Some(header.protocol),
+            LinkHeader::LinuxSll2(header) => Some(header.protocol),
+            LinkHeader::Null(header) | LinkHeader::Loop(header) => header.ether_type(),
+            LinkHeader::Raw => None
+        }
+    }
+
+    pub fn vlan_tags(&self) -> &[VlanTag] {
+        match self {
+            LinkHeader::Ethernet2(header) => &header.vlan_tags,
+            _ => &[]
+        }
+    }
+}
+
+// Finds the network layer of a frame without decoding it, returning its
+// ethertype and offset. Raw IP and MPLS payloads are told apart by the version nibble.
+pub fn network_layer(link_type: u16, data: &[u8]) -> Option<(u16, usize)> {
+    let (ether_type, mut offset) = link_payload(link_type, data)?;
+    if !MplsLabel::is_mpls(ether_type) {
+        return Some((ether_type, offset));
+    }
+
+    while *data.get(offset + 2)? & 0x01 == 0 {
+        offset += 4;
+    }
+    offset += 4;
+    match *data.get(offset)? >> 4 {
+        4 => Some((ETHERTYPE_IPV4, offset)),
+        6 => Some((ETHERTYPE_IPV6, offset)),
+        _ => None
+    }
+}
+
+fn link_payload(link_type: u16, data: &[u8]) -> Option<(u16, usize)> {
+    let be16 = |offset: usize| Some(u16::from_be_bytes([*data.get(offset)?, *data.get(offset + 1)?]));
+
+    match link_type {
+        LINKTYPE_ETHERNET => {
+            let mut offset = 12;
+            while VlanTag::is_tag(be16(offset)?) {
+                offset += 4;
+            }
+            let ether_type = be16(offset)?;
+            if ether_type > MAX_802_3_LENGTH {
+                return Some((ether_type, offset + 2));
+            }
+            // Only SNAP with a zero OUI carries an ethertype, and only if the
+            // 802.3 length covers it rather than padding.
+            if ether_type < 8 {
+                return None;
+            }
+            match data.get(offset + 2..offset + 10)? {
+                [SAP_SNAP, SAP_SNAP, 0x03, 0, 0, 0, ..] => Some((be16(offset + 8)?, offset + 10)),
+                _ => None
+            }
+        }
+        LINKTYPE_LINUX_SLL => Some((be16(14)?, 16)),
+        LINKTYPE_LINUX_SLL2 => Some((be16(0)?, 20)),
+        LINKTYPE_NULL => {
+            let family = u32::from_ne_bytes(data.get(..4)?.try_into().ok()?);
+            Some((NullHeader { family }.ether_type()?, 4))
+        }
+        LINKTYPE_LOOP => {
+            let family = u32::from_be_bytes(data.get(..4)?.try_into().ok()?);
+            Some((NullHeader { family }.ether_type()?, 4))
+        }
+        LINKTYPE_RAW | DLT_RAW | DLT_RAW_OPENBSD => match *data.first()? >> 4 {
+            4 => Some((ETHERTYPE_IPV4, 0)),
+            6 => Some((ETHERTYPE_IPV6, 0)),
+            _ => None
+        },
+        _ => None
+    }
+}
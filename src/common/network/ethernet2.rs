@@ -1,26 +1,153 @@
-use std::mem;
-
 use serde::Serialize;
+use crate::common::network::llc::LlcHeader;
 use crate::common::network::packet::PacketReader;
 use crate::common::network::ReadError;
 
+pub const ETHERTYPE_VLAN: u16 = 0x8100;
+pub const ETHERTYPE_QINQ: u16 = 0x88A8;
+// Used for QinQ before 802.1ad was standardized.
+pub const ETHERTYPE_QINQ_LEGACY: u16 = 0x9100;
+// Larger values of the type field are lengths of 802.3 frames.
+pub const MAX_802_3_LENGTH: u16 = 1500;
+
 #[derive(Serialize)]
 pub struct Ethernet2Header {
     pub destination: [u8; 6],
     pub source: [u8; 6],
-    pub ether_type: u16
+    // Outermost tag first.
+    pub vlan_tags: Vec<VlanTag>,
+    // The ethertype after all VLAN tags, or the length of an 802.3 frame.
+    pub ether_type: u16,
+    // Filled in by `Packet`, which keeps the frame when it can't be read.
+    pub llc: Option<LlcHeader>
 }
 
 impl Ethernet2Header {
-    const SIZE: usize = mem::size_of::<Ethernet2Header>();
+    const SIZE: usize = 14;
 
     pub fn new<'a, 'b: 'a>(packet_reader: &'a mut PacketReader<'b>) -> Result<Ethernet2Header, ReadError> {
         let bytes = packet_reader.read(Self::SIZE)?;
 
-        Ok(Ethernet2Header {
+        let mut header = Ethernet2Header {
             destination: bytes[..6].try_into()?,
             source: bytes[6..12].try_into()?,
-            ether_type: u16::from_be_bytes(bytes[12..14].try_into()?)
-        })
+            vlan_tags: vec![],
+            ether_type: u16::from_be_bytes(bytes[12..14].try_into()?),
+            llc: None
+        };
+        while VlanTag::is_tag(header.ether_type) {
+            let bytes = packet_reader.read(VlanTag::SIZE)?;
+            header.vlan_tags.push(VlanTag::new(header.ether_type, u16::from_be_bytes(bytes[..2].try_into()?)));
+            header.ether_type = u16::from_be_bytes(bytes[2..4].try_into()?);
+        }
+        // Short 802.3 frames are padded, and the padding isn't payload.
+        if header.is_802_3() {
+            packet_reader.limit_payload(header.ether_type as usize);
+        }
+        Ok(header)
+    }
+
+    pub fn is_802_3(&self) -> bool {
+        self.ether_type <= MAX_802_3_LENGTH
+    }
+
+    // What the frame carries, from the SNAP header for 802.3 frames. None for
+    // LLC protocols without an ethertype, such as STP.
+    pub fn payload_type(&self) -> Option<u16> {
+        match &self.llc {
+            Some(llc) => llc.snap.as_ref()?.ether_type(),
+            None if self.is_802_3() => None,
+            None => Some(self.ether_type)
+        }
+    }
+
+    pub fn vlan_ids(&self) -> Vec<u16> {
+        self.vlan_tags.iter().map(|tag| tag.vid).collect()
+    }
+}
+
+#[derive(Serialize)]
+pub struct VlanTag {
+    pub tpid: u16,
+    pub pcp: u8,
+    pub dei: bool,
+    pub vid: u16
+}
+
+impl VlanTag {
+    const SIZE: usize = 4;
+
+    pub fn is_tag(ether_type: u16) -> bool {
+        matches!(ether_type, ETHERTYPE_VLAN | ETHERTYPE_QINQ | ETHERTYPE_QINQ_LEGACY)
+    }
+
+    pub fn new(tpid: u16, tci: u16) -> VlanTag {
+        VlanTag {
+            tpid,
+            pcp: (tci >> 13) as u8,
+            dei: tci & 0x1000 != 0,
+            vid: tci & 0x0FFF
+        }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::fixtures::{ethernet, ipv4, udp};
+    use crate::common::network::datalink::{network_layer, ETHERTYPE_IPV4, LINKTYPE_ETHERNET};
+    use crate::common::network::ethernet2::{ETHERTYPE_QINQ, ETHERTYPE_VLAN};
+    use crate::common::network::packet::Packet;
+    use crate::common::network::ReadError;
+
+    fn tagged(tags: &[(u16, u16)], ether_type: u16, payload: &[u8]) -> Vec<u8> {
+        let (first_tpid, _) = tags[0];
+        let mut rest = vec![];
+        for (index, (_, tci)) in tags.iter().enumerate() {
+            rest.extend(tci.to_be_bytes());
+            let next = tags.get(index + 1).map_or(ether_type, |(tpid, _)| *tpid);
+            rest.extend(next.to_be_bytes());
+        }
+        rest.extend(payload);
+        ethernet(first_tpid, &rest)
+    }
+
+    fn datagram() -> Vec<u8> {
+        ipv4(17, [10, 0, 0, 1], [10, 0, 0, 2], &udp(1, 2, &[]))
+    }
+
+    #[test]
+    fn decodes_vlan_tag() {
+        // Priority 5, drop eligible, VLAN 100.
+        let frame = tagged(&[(ETHERTYPE_VLAN, 0xB064)], ETHERTYPE_IPV4, &datagram());
+        let packet = Packet::from_ethernet_bytes(&frame).unwrap();
+
+        let tag = &packet.lp_header.vlan_tags()[0];
+        assert_eq!((tag.tpid, tag.pcp, tag.dei, tag.vid), (ETHERTYPE_VLAN, 5, true, 100));
+        assert!(packet.ip_header().is_some());
+        assert!(packet.trailer.is_empty());
+        assert_eq!(network_layer(LINKTYPE_ETHERNET, &frame), Some((ETHERTYPE_IPV4, 18)));
+    }
+
+    #[test]
+    fn decodes_qinq_outermost_first() {
+        let frame = tagged(&[(ETHERTYPE_QINQ, 10), (ETHERTYPE_VLAN, 20)], ETHERTYPE_IPV4, &datagram());
+        let packet = Packet::from_ethernet_bytes(&frame).unwrap();
+
+        let tpids: Vec<u16> = packet.lp_header.vlan_tags().iter().map(|tag| tag.tpid).collect();
+        assert_eq!(tpids, [ETHERTYPE_QINQ, ETHERTYPE_VLAN]);
+        assert_eq!(packet.vlan_ids(), [10, 20]);
+        assert!(packet.tp_header.is_some());
+        assert_eq!(network_layer(LINKTYPE_ETHERNET, &frame), Some((ETHERTYPE_IPV4, 22)));
+    }
+
+    #[test]
+    fn rejects_truncated_tag() {
+        let mut frame = ethernet(ETHERTYPE_VLAN, &[0, 100]);
+        assert!(matches!(Packet::from_ethernet_bytes(&frame), Err(ReadError::DataOffsetTooSmall(2))));
+        assert_eq!(network_layer(LINKTYPE_ETHERNET, &frame), None);
+
+        frame.truncate(13);
+        assert!(matches!(Packet::from_ethernet_bytes(&frame), Err(ReadError::DataOffsetTooSmall(1))));
+        assert_eq!(network_layer(LINKTYPE_ETHERNET, &frame), None);
+    }
+}
@@ -1 +1,32 @@
-pub mod internet;
\ No newline at end of file
+use serde::Serialize;
+use crate::common::network::link::arp::ArpHeader;
+use crate::common::network::link::internet::IpHeader;
+
+pub mod arp;
+pub mod internet;
+
+#[derive(Serialize)]
+pub enum NetworkHeader {
+    Ip(IpHeader),
+    Arp(ArpHeader),
+    // Not understood or not decodable. The ethertype is missing when the link
+    // layer doesn't name the payload, as after MPLS, for raw IP or for LLC
+    // protocols like STP.
+    Unknown { ether_type: Option<u16>, bytes: Vec<u8> }
+}
+
+impl NetworkHeader {
+    pub fn ip(&self) -> Option<&IpHeader> {
+        match self {
+            NetworkHeader::Ip(header) => Some(header),
+            _ => None
+        }
+    }
+
+    pub fn arp(&self) -> Option<&ArpHeader> {
+        match self {
+            NetworkHeader::Arp(header) => Some(header),
+            _ => None
+        }
+    }
+}
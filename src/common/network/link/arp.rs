@@ -0,0 +1,143 @@
+use std::net::Ipv4Addr;
+
+use serde::Serialize;
+use crate::common::network::datalink::ETHERTYPE_IPV4;
+use crate::common::network::packet::PacketReader;
+use crate::common::network::ReadError;
+
+pub const ETHERTYPE_ARP: u16 = 0x0806;
+pub const ETHERTYPE_RARP: u16 = 0x8035;
+
+pub const ARP_REQUEST: u16 = 1;
+pub const ARP_REPLY: u16 = 2;
+pub const RARP_REQUEST: u16 = 3;
+pub const RARP_REPLY: u16 = 4;
+
+// Address lengths come from the header, so addresses of any hardware and
+// protocol type are kept as they are.
+#[derive(Serialize)]
+pub struct ArpHeader {
+    pub hardware_type: u16,
+    pub protocol_type: u16,
+    pub hardware_len: u8,
+    pub protocol_len: u8,
+    pub operation: u16,
+    pub sender_hardware_addr: Vec<u8>,
+    pub sender_protocol_addr: Vec<u8>,
+    pub target_hardware_addr: Vec<u8>,
+    pub target_protocol_addr: Vec<u8>
+}
+
+impl ArpHeader {
+    const SIZE: usize = 8;
+
+    pub fn is_arp(ether_type: u16) -> bool {
+        matches!(ether_type, ETHERTYPE_ARP | ETHERTYPE_RARP)
+    }
+
+    pub fn new<'a, 'b: 'a>(packet_reader: &'a mut PacketReader<'b>) -> Result<ArpHeader, ReadError> {
+        let bytes = packet_reader.read(Self::SIZE)?;
+        let hardware_len = bytes[4];
+        let protocol_len = bytes[5];
+
+        Ok(ArpHeader {
+            hardware_type: u16::from_be_bytes(bytes[..2].try_into()?),
+            protocol_type: u16::from_be_bytes(bytes[2..4].try_into()?),
+            hardware_len,
+            protocol_len,
+            operation: u16::from_be_bytes(bytes[6..8].try_into()?),
+            sender_hardware_addr: packet_reader.read(hardware_len as usize)?.to_vec(),
+            sender_protocol_addr: packet_reader.read(protocol_len as usize)?.to_vec(),
+            target_hardware_addr: packet_reader.read(hardware_len as usize)?.to_vec(),
+            target_protocol_addr: packet_reader.read(protocol_len as usize)?.to_vec()
+        })
+    }
+
+    pub fn is_reverse(&self) -> bool {
+        matches!(self.operation, RARP_REQUEST | RARP_REPLY)
+    }
+
+    pub fn sender_ipv4(&self) -> Option<Ipv4Addr> {
+        ipv4(self.protocol_type, &self.sender_protocol_addr)
+    }
+
+    pub fn target_ipv4(&self) -> Option<Ipv4Addr> {
+        ipv4(self.protocol_type, &self.target_protocol_addr)
+    }
+}
+
+fn ipv4(protocol_type: u16, addr: &[u8]) -> Option<Ipv4Addr> {
+    if protocol_type != ETHERTYPE_IPV4 {
+        return None;
+    }
+    let octets: [u8; 4] = addr.try_into().ok()?;
+    Some(Ipv4Addr::from(octets))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use crate::common::fixtures::{ethernet, MAC_A, MAC_B};
+    use crate::common::network::datalink::{network_layer, ETHERTYPE_IPV4, ETHERTYPE_IPV6, LINKTYPE_ETHERNET};
+    use crate::common::network::link::arp::{ARP_REQUEST, ETHERTYPE_ARP, ETHERTYPE_RARP, RARP_REPLY};
+    use crate::common::network::link::NetworkHeader;
+    use crate::common::network::packet::Packet;
+    use crate::common::network::ReadError;
+
+    fn arp(protocol_type: u16, operation: u16, sender: &[u8], target: &[u8]) -> Vec<u8> {
+        let mut message = vec![0, 1];
+        message.extend(protocol_type.to_be_bytes());
+        message.extend([6, sender.len() as u8]);
+        message.extend(operation.to_be_bytes());
+        message.extend(MAC_A);
+        message.extend(sender);
+        message.extend(MAC_B);
+        message.extend(target);
+        message
+    }
+
+    #[test]
+    fn decodes_request_and_keeps_padding_out() {
+        let mut frame = ethernet(ETHERTYPE_ARP, &arp(ETHERTYPE_IPV4, ARP_REQUEST, &[10, 0, 0, 1], &[10, 0, 0, 2]));
+        frame.resize(60, 0);
+        let packet = Packet::from_ethernet_bytes(&frame).unwrap();
+
+        let header = packet.arp_header().unwrap();
+        assert_eq!(header.operation, ARP_REQUEST);
+        assert!(!header.is_reverse());
+        assert_eq!(header.sender_hardware_addr, MAC_A);
+        assert_eq!(header.sender_ipv4(), Some(Ipv4Addr::new(10, 0, 0, 1)));
+        assert_eq!(header.target_ipv4(), Some(Ipv4Addr::new(10, 0, 0, 2)));
+        assert_eq!(packet.trailer, [0; 18]);
+        assert_eq!(network_layer(LINKTYPE_ETHERNET, &frame), Some((ETHERTYPE_ARP, 14)));
+    }
+
+    #[test]
+    fn decodes_reverse_arp() {
+        let frame = ethernet(ETHERTYPE_RARP, &arp(ETHERTYPE_IPV4, RARP_REPLY, &[10, 0, 0, 1], &[10, 0, 0, 2]));
+        let packet = Packet::from_ethernet_bytes(&frame).unwrap();
+        assert!(packet.arp_header().unwrap().is_reverse());
+    }
+
+    #[test]
+    fn keeps_addresses_of_other_protocols() {
+        let frame = ethernet(ETHERTYPE_ARP, &arp(ETHERTYPE_IPV6, ARP_REQUEST, &[1; 16], &[2; 16]));
+        let packet = Packet::from_ethernet_bytes(&frame).unwrap();
+
+        let header = packet.arp_header().unwrap();
+        assert_eq!((header.sender_protocol_addr.len(), header.target_protocol_addr.len()), (16, 16));
+        assert_eq!(header.sender_ipv4(), None);
+    }
+
+    #[test]
+    fn truncated_message_is_unknown() {
+        let message = arp(ETHERTYPE_IPV4, ARP_REQUEST, &[10, 0, 0, 1], &[10, 0, 0, 2]);
+        let frame = ethernet(ETHERTYPE_ARP, &message[..20]);
+        let packet = Packet::from_ethernet_bytes(&frame).unwrap();
+
+        assert!(matches!(&packet.network_header, Some(NetworkHeader::Unknown { ether_type: Some(ETHERTYPE_ARP), bytes }) if bytes.len() == 20));
+        assert!(matches!(packet.errors[..], [ReadError::DataOffsetTooSmall(_)]));
+        assert_eq!(network_layer(LINKTYPE_ETHERNET, &frame), Some((ETHERTYPE_ARP, 14)));
+    }
+}
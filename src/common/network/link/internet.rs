@@ -34,6 +34,16 @@ impl IpHeader {
         }
     }
 
+    // Header and payload as the header states them, without link layer
+    // padding. IPv6 jumbograms give their length in an extension instead.
+    pub fn total_len(&self) -> Option<usize> {
+        match self {
+            V4Header(header) => Some(header.total_length as usize),
+            V6Header(header) if header.payload_length > 0 => Some(40 + header.payload_length as usize),
+            V6Header(_) => None
+        }
+    }
+
     pub fn protocol(&self) -> u8 {
         match self {
             V4Header(header) => header.protocol,
@@ -82,14 +92,18 @@ impl IpExtension {
         }
     }
 
+    // Stops at the first header that isn't a supported extension, but fails
+    // when an extension is cut short.
     pub fn list<'a, 'b: 'a>(mut next_header: u8, packet_reader: &'a mut PacketReader<'b>) -> Result<(u8, Vec<IpExtension>), ReadError> {
         let mut res = vec![];
         loop {
-            if let Ok(extension) = IpExtension::new(next_header, packet_reader) {
-                next_header = extension.next_header();
-                res.push(extension);
-            } else {
-                break;
+            match IpExtension::new(next_header, packet_reader) {
+                Ok(extension) => {
+                    next_header = extension.next_header();
+                    res.push(extension);
+                }
+                Err(ReadError::UnsupportedIpExtension) => break,
+                Err(e) => return Err(e)
             }
         }
         Ok((next_header, res))
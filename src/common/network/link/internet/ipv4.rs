@@ -26,7 +26,7 @@ impl Ipv4Header {
         let bytes = packet_reader.read(Self::SIZE)?;
 
         let ihl = bytes[0] & 0x0F;
-        let options_size = TryInto::<usize>::try_into(ihl)? * 4 - Self::SIZE;
+        let options_size = (TryInto::<usize>::try_into(ihl)? * 4).checked_sub(Self::SIZE).ok_or(ReadError::CouldntParse)?;
         let options = if options_size > 0 {
             packet_reader.read(options_size)?.to_vec()
         } else {
@@ -13,7 +13,7 @@ pub mod udp;
 pub enum TransportHeader {
     TCP(TCPHeader),
     UDP(UDPHeader),
-    Default(Vec<u8>)
+    Unknown { protocol: u8, bytes: Vec<u8> }
 }
 
 impl TransportHeader {
@@ -21,7 +21,7 @@ impl TransportHeader {
         Ok(match protocol {
             6 => TransportHeader::TCP(TCPHeader::new(packet_reader)?),
             17 => TransportHeader::UDP(UDPHeader::new(packet_reader)?),
-            _ => TransportHeader::Default(vec![])
+            protocol => TransportHeader::Unknown { protocol, bytes: packet_reader.rest().to_vec() }
         })
     }
 
@@ -29,7 +29,7 @@ impl TransportHeader {
         match self {
             TransportHeader::TCP(tcp) => tcp.src_port,
             TransportHeader::UDP(udp) => udp.src_port,
-            TransportHeader::Default(_) => 0
+            TransportHeader::Unknown { .. } => 0
         }
     }
 
@@ -37,7 +37,7 @@ impl TransportHeader {
         match self {
             TransportHeader::TCP(tcp) => tcp.dst_port,
             TransportHeader::UDP(udp) => udp.dst_port,
-            TransportHeader::Default(_) => 0
+            TransportHeader::Unknown { .. } => 0
         }
     }
 }
@@ -4,11 +4,11 @@ use crate::network::ReadError;
 
 #[derive(Serialize)]
 pub enum ApplicationHeader {
-    Default
+    Unknown { bytes: Vec<u8> }
 }
 
 impl ApplicationHeader {
-    pub fn new<'a, 'b: 'a>(_packet_reader: &'a mut PacketReader<'b>) -> Result<ApplicationHeader, ReadError> {
-        Ok(ApplicationHeader::Default)
+    pub fn new<'a, 'b: 'a>(packet_reader: &'a mut PacketReader<'b>) -> Result<ApplicationHeader, ReadError> {
+        Ok(ApplicationHeader::Unknown { bytes: packet_reader.rest().to_vec() })
     }
 }
\ No newline at end of file
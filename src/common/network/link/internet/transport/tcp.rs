@@ -24,7 +24,7 @@ impl TCPHeader {
         let bytes = packet_reader.read(Self::SIZE)?;
 
         let data_offset = bytes[12] >> 4;
-        let options_size = TryInto::<usize>::try_into(data_offset)? * 4 - Self::SIZE;
+        let options_size = (TryInto::<usize>::try_into(data_offset)? * 4).checked_sub(Self::SIZE).ok_or(ReadError::CouldntParse)?;
         let options = if options_size > 0 {
             packet_reader.read(options_size)?.to_vec()
         } else {
@@ -0,0 +1,135 @@
+use serde::Serialize;
+use crate::common::network::packet::PacketReader;
+use crate::common::network::ReadError;
+
+pub const SAP_SNAP: u8 = 0xAA;
+
+// IEEE 802.2 header of 802.3 frames. The control field is one byte for
+// unnumbered frames and two for information and supervisory ones.
+#[derive(Serialize)]
+pub struct LlcHeader {
+    pub dsap: u8,
+    pub ssap: u8,
+    pub control: u16,
+    pub snap: Option<SnapHeader>
+}
+
+#[derive(Serialize)]
+pub struct SnapHeader {
+    pub oui: [u8; 3],
+    pub pid: u16
+}
+
+impl LlcHeader {
+    const SIZE: usize = 3;
+
+    pub fn new<'a, 'b: 'a>(packet_reader: &'a mut PacketReader<'b>) -> Result<LlcHeader, ReadError> {
+        let bytes = packet_reader.read(Self::SIZE)?;
+        let (dsap, ssap) = (bytes[0], bytes[1]);
+
+        let control = if bytes[2] & 0x03 == 0x03 {
+            u16::from(bytes[2])
+        } else {
+            u16::from_be_bytes([bytes[2], packet_reader.read(1)?[0]])
+        };
+        let snap = if dsap == SAP_SNAP && ssap == SAP_SNAP {
+            Some(SnapHeader::new(packet_reader)?)
+        } else {
+            None
+        };
+
+        Ok(LlcHeader {
+            dsap,
+            ssap,
+            control,
+            snap
+        })
+    }
+}
+
+impl SnapHeader {
+    const SIZE: usize = 5;
+
+    pub fn new<'a, 'b: 'a>(packet_reader: &'a mut PacketReader<'b>) -> Result<SnapHeader, ReadError> {
+        let bytes = packet_reader.read(Self::SIZE)?;
+
+        Ok(SnapHeader {
+            oui: bytes[..3].try_into()?,
+            pid: u16::from_be_bytes(bytes[3..5].try_into()?)
+        })
+    }
+
+    // With a zero OUI the protocol ID is an ethertype (RFC 1042), otherwise
+    // it is defined by the organization, like 0x2000 for Cisco's CDP.
+    pub fn ether_type(&self) -> Option<u16> {
+        if self.oui == [0, 0, 0] { Some(self.pid) } else { None }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::fixtures::{ethernet, ipv4, udp};
+    use crate::common::network::datalink::{network_layer, LinkHeader, ETHERTYPE_IPV4, LINKTYPE_ETHERNET};
+    use crate::common::network::link::NetworkHeader;
+    use crate::common::network::llc::SAP_SNAP;
+    use crate::common::network::packet::Packet;
+    use crate::common::network::ReadError;
+
+    // An 802.3 frame padded to the Ethernet minimum of 60 bytes.
+    fn ieee802_3(payload: &[u8]) -> Vec<u8> {
+        let mut frame = ethernet(payload.len() as u16, payload);
+        frame.resize(frame.len().max(60), 0);
+        frame
+    }
+
+    #[test]
+    fn decodes_snap_payload_and_keeps_padding_out() {
+        let mut payload = vec![SAP_SNAP, SAP_SNAP, 0x03, 0, 0, 0, 0x08, 0x00];
+        payload.extend(ipv4(17, [10, 0, 0, 1], [10, 0, 0, 2], &udp(1, 2, &[])));
+        let frame = ieee802_3(&payload);
+        let packet = Packet::from_ethernet_bytes(&frame).unwrap();
+
+        assert!(packet.ip_header().is_some());
+        assert!(packet.tp_header.is_some());
+        assert!(packet.errors.is_empty());
+        assert_eq!(packet.trailer, [0; 10]);
+        assert_eq!(network_layer(LINKTYPE_ETHERNET, &frame), Some((ETHERTYPE_IPV4, 22)));
+    }
+
+    #[test]
+    fn leaves_stp_undecoded() {
+        let bpdu = [0x42, 0x42, 0x03, 0, 0, 0, 0];
+        let frame = ieee802_3(&bpdu);
+        let packet = Packet::from_ethernet_bytes(&frame).unwrap();
+
+        assert!(matches!(&packet.network_header, Some(NetworkHeader::Unknown { ether_type: None, bytes }) if bytes[..] == bpdu[3..]));
+        assert!(packet.errors.is_empty());
+        assert_eq!(packet.trailer.len(), 60 - 14 - bpdu.len());
+        assert_eq!(network_layer(LINKTYPE_ETHERNET, &frame), None);
+    }
+
+    #[test]
+    fn two_byte_control_field() {
+        let frame = ieee802_3(&[0xF0, 0xF0, 0x00, 0x02, 0xFF]);
+        let packet = Packet::from_ethernet_bytes(&frame).unwrap();
+
+        let llc = match &packet.lp_header {
+            LinkHeader::Ethernet2(header) => header.llc.as_ref().unwrap(),
+            _ => unreachable!()
+        };
+        assert_eq!(llc.control, 0x0002);
+        assert!(matches!(&packet.network_header, Some(NetworkHeader::Unknown { ether_type: None, bytes }) if bytes[..] == [0xFF]));
+    }
+
+    #[test]
+    fn truncated_llc_leaves_payload_unknown() {
+        // The length cuts the SNAP header short, the rest is padding.
+        let frame = ieee802_3(&[SAP_SNAP, SAP_SNAP, 0x03, 0, 0]);
+        let packet = Packet::from_ethernet_bytes(&frame).unwrap();
+
+        assert!(matches!(&packet.network_header, Some(NetworkHeader::Unknown { ether_type: None, bytes }) if bytes.len() == 5));
+        assert!(matches!(packet.errors[..], [ReadError::DataOffsetTooSmall(_)]));
+        assert_eq!(packet.trailer.len(), 60 - 14 - 5);
+        assert_eq!(network_layer(LINKTYPE_ETHERNET, &frame), None);
+    }
+}
@@ -0,0 +1,102 @@
+use serde::Serialize;
+use crate::common::network::datalink::{ETHERTYPE_IPV4, ETHERTYPE_IPV6};
+use crate::common::network::packet::PacketReader;
+use crate::common::network::ReadError;
+
+// BSD loopback header: a 4 byte address family, in the capturing host's byte
+// order for DLT_NULL and in network byte order for DLT_LOOP.
+#[derive(Serialize)]
+pub struct NullHeader {
+    pub family: u32
+}
+
+impl NullHeader {
+    const SIZE: usize = 4;
+
+    pub fn new_host_order<'a, 'b: 'a>(packet_reader: &'a mut PacketReader<'b>) -> Result<NullHeader, ReadError> {
+        let bytes = packet_reader.read(Self::SIZE)?;
+        let family = u32::from_ne_bytes(bytes.try_into()?);
+
+        // Savefiles may come from a host of the other byte order, in which
+        // case the family ends up in the upper half.
+        Ok(NullHeader {
+            family: if family & 0xFFFF == 0 { family.swap_bytes() } else { family }
+        })
+    }
+
+    pub fn new_network_order<'a, 'b: 'a>(packet_reader: &'a mut PacketReader<'b>) -> Result<NullHeader, ReadError> {
+        let bytes = packet_reader.read(Self::SIZE)?;
+
+        Ok(NullHeader {
+            family: u32::from_be_bytes(bytes.try_into()?)
+        })
+    }
+
+    // AF_INET6 differs between the BSDs, Darwin and Linux.
+    pub fn ether_type(&self) -> Option<u16> {
+        let family = if self.family & 0xFFFF == 0 { self.family.swap_bytes() } else { self.family };
+        match family {
+            2 => Some(ETHERTYPE_IPV4),
+            10 | 24 | 28 | 30 => Some(ETHERTYPE_IPV6),
+            _ => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::fixtures::{ipv4, udp};
+    use crate::common::network::datalink::{network_layer, ETHERTYPE_IPV4, LINKTYPE_LOOP, LINKTYPE_NULL};
+    use crate::common::network::link::NetworkHeader;
+    use crate::common::network::packet::Packet;
+    use crate::common::network::ReadError;
+
+    fn with_family(family: [u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut frame = family.to_vec();
+        frame.extend(payload);
+        frame
+    }
+
+    fn datagram() -> Vec<u8> {
+        ipv4(17, [127, 0, 0, 1], [127, 0, 0, 1], &udp(1, 2, &[]))
+    }
+
+    #[test]
+    fn decodes_null_in_either_byte_order() {
+        for family in [2u32.to_ne_bytes(), 2u32.swap_bytes().to_ne_bytes()] {
+            let frame = with_family(family, &datagram());
+            let packet = Packet::from_bytes(LINKTYPE_NULL, &frame).unwrap();
+
+            assert_eq!(packet.lp_header.ether_type(), Some(ETHERTYPE_IPV4));
+            assert!(packet.tp_header.is_some());
+            assert_eq!(network_layer(LINKTYPE_NULL, &frame), Some((ETHERTYPE_IPV4, 4)));
+        }
+    }
+
+    #[test]
+    fn decodes_loop_in_network_order() {
+        let frame = with_family(2u32.to_be_bytes(), &datagram());
+        let packet = Packet::from_bytes(LINKTYPE_LOOP, &frame).unwrap();
+
+        assert!(packet.tp_header.is_some());
+        assert_eq!(network_layer(LINKTYPE_LOOP, &frame), Some((ETHERTYPE_IPV4, 4)));
+    }
+
+    #[test]
+    fn leaves_unknown_family_undecoded() {
+        // AF_APPLETALK names no ethertype, so nothing failed.
+        let frame = with_family(16u32.to_be_bytes(), &[1, 2, 3]);
+        let packet = Packet::from_bytes(LINKTYPE_LOOP, &frame).unwrap();
+
+        assert!(matches!(&packet.network_header, Some(NetworkHeader::Unknown { ether_type: None, bytes }) if bytes[..] == [1, 2, 3]));
+        assert!(packet.errors.is_empty());
+        assert_eq!(network_layer(LINKTYPE_LOOP, &frame), None);
+    }
+
+    #[test]
+    fn rejects_truncated_header() {
+        let frame = [2, 0, 0];
+        assert!(matches!(Packet::from_bytes(LINKTYPE_NULL, &frame), Err(ReadError::DataOffsetTooSmall(1))));
+        assert_eq!(network_layer(LINKTYPE_NULL, &frame), None);
+    }
+}
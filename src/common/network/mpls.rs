@@ -0,0 +1,104 @@
+use serde::Serialize;
+use crate::common::network::packet::PacketReader;
+use crate::common::network::ReadError;
+
+pub const ETHERTYPE_MPLS_UNICAST: u16 = 0x8847;
+pub const ETHERTYPE_MPLS_MULTICAST: u16 = 0x8848;
+
+#[derive(Serialize)]
+pub struct MplsLabel {
+    pub label: u32,
+    pub traffic_class: u8,
+    pub bottom_of_stack: bool,
+    pub ttl: u8
+}
+
+impl MplsLabel {
+    const SIZE: usize = 4;
+
+    pub fn is_mpls(ether_type: u16) -> bool {
+        matches!(ether_type, ETHERTYPE_MPLS_UNICAST | ETHERTYPE_MPLS_MULTICAST)
+    }
+
+    pub fn new<'a, 'b: 'a>(packet_reader: &'a mut PacketReader<'b>) -> Result<MplsLabel, ReadError> {
+        let entry = u32::from_be_bytes(packet_reader.read(Self::SIZE)?.try_into()?);
+
+        Ok(MplsLabel {
+            label: entry >> 12,
+            traffic_class: ((entry >> 9) & 0x07) as u8,
+            bottom_of_stack: entry & 0x100 != 0,
+            ttl: entry as u8
+        })
+    }
+
+    // Reads labels up to and including the one marked bottom of stack.
+    pub fn stack<'a, 'b: 'a>(packet_reader: &'a mut PacketReader<'b>) -> Result<Vec<MplsLabel>, ReadError> {
+        let mut labels = vec![];
+        loop {
+            let label = MplsLabel::new(packet_reader)?;
+            let bottom_of_stack = label.bottom_of_stack;
+            labels.push(label);
+            if bottom_of_stack {
+                return Ok(labels);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::fixtures::{ethernet, ipv4, udp};
+    use crate::common::network::datalink::{network_layer, ETHERTYPE_IPV4, LINKTYPE_ETHERNET};
+    use crate::common::network::link::NetworkHeader;
+    use crate::common::network::mpls::ETHERTYPE_MPLS_UNICAST;
+    use crate::common::network::packet::Packet;
+    use crate::common::network::ReadError;
+
+    fn label(label: u32, bottom_of_stack: bool) -> [u8; 4] {
+        (label << 12 | (bottom_of_stack as u32) << 8 | 64).to_be_bytes()
+    }
+
+    fn mpls(labels: &[[u8; 4]], payload: &[u8]) -> Vec<u8> {
+        let mut stack = labels.concat();
+        stack.extend(payload);
+        ethernet(ETHERTYPE_MPLS_UNICAST, &stack)
+    }
+
+    #[test]
+    fn decodes_ip_after_label_stack() {
+        let frame = mpls(&[label(100, false), label(200, true)], &ipv4(17, [10, 0, 0, 1], [10, 0, 0, 2], &udp(1, 2, &[])));
+        let packet = Packet::from_ethernet_bytes(&frame).unwrap();
+
+        let labels: Vec<u32> = packet.mpls_labels.iter().map(|label| label.label).collect();
+        assert_eq!(labels, [100, 200]);
+        assert!(packet.ip_header().is_some());
+        assert!(packet.tp_header.is_some());
+        assert!(packet.errors.is_empty());
+        assert_eq!(network_layer(LINKTYPE_ETHERNET, &frame), Some((ETHERTYPE_IPV4, 22)));
+    }
+
+    #[test]
+    fn leaves_pseudowire_undecoded() {
+        // A control word followed by an Ethernet frame.
+        let inner = ethernet(0x0800, &[]);
+        let frame = mpls(&[label(300, true)], &[&[0u8; 4][..], &inner].concat());
+        let packet = Packet::from_ethernet_bytes(&frame).unwrap();
+
+        assert_eq!(packet.mpls_labels.len(), 1);
+        assert!(matches!(&packet.network_header, Some(NetworkHeader::Unknown { ether_type: None, bytes }) if bytes.len() == 18));
+        assert!(packet.errors.is_empty());
+        assert_eq!(network_layer(LINKTYPE_ETHERNET, &frame), None);
+    }
+
+    #[test]
+    fn truncated_stack_is_unknown() {
+        let frame = mpls(&[label(100, false)], &[0x45]);
+        let packet = Packet::from_ethernet_bytes(&frame).unwrap();
+
+        assert!(packet.mpls_labels.is_empty());
+        assert!(matches!(&packet.network_header,
+            Some(NetworkHeader::Unknown { ether_type: Some(ETHERTYPE_MPLS_UNICAST), bytes }) if bytes.len() == 5));
+        assert!(matches!(packet.errors[..], [ReadError::DataOffsetTooSmall(_)]));
+        assert_eq!(network_layer(LINKTYPE_ETHERNET, &frame), None);
+    }
+}
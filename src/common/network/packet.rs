@@ -1,52 +1,220 @@
 use serde::Serialize;
-use crate::network::ethernet2::Ethernet2Header;
+use crate::network::datalink::{LinkHeader, ETHERTYPE_IPV4, ETHERTYPE_IPV6, LINKTYPE_ETHERNET};
+use crate::network::link::arp::ArpHeader;
 use crate::network::link::internet::{IpExtension, IpHeader};
 use crate::network::link::internet::transport::application::ApplicationHeader;
 use crate::network::link::internet::transport::TransportHeader;
+use crate::network::link::NetworkHeader;
+use crate::network::llc::LlcHeader;
+use crate::network::mpls::MplsLabel;
 use crate::network::ReadError;
 
+// Only the link layer is required. Every layer above it is decoded as far as
+// it is understood: a layer that can't be decoded keeps its bytes as
+// `Unknown` and nothing above it is attempted, so every captured byte ends up
+// in some layer or in the trailer.
 #[derive(Serialize)]
 pub struct Packet {
-    pub lp_header: Ethernet2Header,
-    pub ip_header: IpHeader,
+    pub lp_header: LinkHeader,
+    pub mpls_labels: Vec<MplsLabel>,
+    pub network_header: Option<NetworkHeader>,
     pub ip_extensions: Vec<IpExtension>,
-    pub tp_header: TransportHeader,
-    pub ap_header: ApplicationHeader
+    pub tp_header: Option<TransportHeader>,
+    pub ap_header: Option<ApplicationHeader>,
+    // Bytes after the last decoded layer, such as Ethernet padding after the
+    // length an IP header gives.
+    pub trailer: Vec<u8>,
+    // Why layers were left `Unknown` or undecoded.
+    #[serde(skip)]
+    pub errors: Vec<ReadError>
 }
 
 impl Packet {
     pub fn from_ethernet_bytes(bytes: &[u8]) -> Result<Packet, ReadError> {
+        Packet::from_bytes(LINKTYPE_ETHERNET, bytes)
+    }
+
+    pub fn from_bytes(link_type: u16, bytes: &[u8]) -> Result<Packet, ReadError> {
         let mut packet_reader = PacketReader::new(bytes);
 
-        let lp_header = Ethernet2Header::new(&mut packet_reader)?;
-        let ip_header = IpHeader::new(packet_reader.peek(1)?[0] >> 4, &mut packet_reader)?;
-        let (protocol, ip_extensions) = IpExtension::list(ip_header.protocol(), &mut packet_reader)?;
-        Ok(Packet {
-            lp_header,
-            ip_header,
-            ip_extensions,
-            tp_header: TransportHeader::new(protocol, &mut packet_reader)?,
-            ap_header: ApplicationHeader::new(&mut packet_reader)?
-        })
+        let mut packet = Packet {
+            lp_header: LinkHeader::new(link_type, &mut packet_reader)?,
+            mpls_labels: vec![],
+            network_header: None,
+            ip_extensions: vec![],
+            tp_header: None,
+            ap_header: None,
+            trailer: vec![],
+            errors: vec![]
+        };
+        packet.decode_network(&mut packet_reader);
+        packet.trailer = packet_reader.trailer().to_vec();
+        Ok(packet)
+    }
+
+    fn decode_network(&mut self, packet_reader: &mut PacketReader) {
+        if let Err(e) = self.decode_llc(packet_reader) {
+            self.unknown_network(None, e, packet_reader);
+            return;
+        }
+
+        let mut ether_type = self.lp_header.ether_type();
+        // Only raw IP leaves the payload unnamed on purpose.
+        let mut guess_ip = matches!(self.lp_header, LinkHeader::Raw);
+
+        // MPLS doesn't say what it carries, so after its last label the
+        // payload is only taken for IP if the version nibble says so. Anything
+        // else, like an Ethernet pseudowire, is left undecoded without an error.
+        if ether_type.is_some_and(MplsLabel::is_mpls) {
+            let start = packet_reader.position;
+            match MplsLabel::stack(packet_reader) {
+                Ok(labels) => {
+                    self.mpls_labels = labels;
+                    ether_type = None;
+                    guess_ip = matches!(packet_reader.peek(1).map(|bytes| bytes[0] >> 4), Ok(4 | 6));
+                }
+                Err(e) => {
+                    packet_reader.position = start;
+                    self.unknown_network(ether_type, e, packet_reader);
+                    return;
+                }
+            }
+        }
+
+        let start = packet_reader.position;
+        let network_header = match ether_type {
+            Some(ether_type) if ArpHeader::is_arp(ether_type) => ArpHeader::new(packet_reader).map(NetworkHeader::Arp),
+            Some(ETHERTYPE_IPV4 | ETHERTYPE_IPV6) => ip_header(packet_reader).map(NetworkHeader::Ip),
+            None if guess_ip => ip_header(packet_reader).map(NetworkHeader::Ip),
+            // Nothing failed, the link layer just doesn't say what follows.
+            None => {
+                self.network_header = Some(NetworkHeader::Unknown { ether_type, bytes: packet_reader.rest().to_vec() });
+                return;
+            }
+            Some(ether_type) => Err(ReadError::UnsupportedEtherType(ether_type))
+        };
+
+        match network_header {
+            Ok(NetworkHeader::Ip(ip_header)) => {
+                if let Some(total_len) = ip_header.total_len() {
+                    packet_reader.limit(start + total_len);
+                }
+                let extensions_start = packet_reader.position;
+                match IpExtension::list(ip_header.protocol(), packet_reader) {
+                    Ok((protocol, ip_extensions)) => {
+                        self.ip_extensions = ip_extensions;
+                        self.network_header = Some(NetworkHeader::Ip(ip_header));
+                        self.decode_transport(protocol, packet_reader);
+                    }
+                    Err(e) => {
+                        packet_reader.position = extensions_start;
+                        self.network_header = Some(NetworkHeader::Ip(ip_header));
+                        self.errors.push(e);
+                    }
+                }
+            }
+            Ok(network_header) => {
+                self.network_header = Some(network_header);
+            }
+            Err(e) => {
+                packet_reader.position = start;
+                self.unknown_network(ether_type, e, packet_reader);
+            }
+        }
+    }
+
+    // The LLC header of 802.3 frames is what names their payload, so a
+    // broken one leaves the payload undecoded rather than the whole frame.
+    fn decode_llc(&mut self, packet_reader: &mut PacketReader) -> Result<(), ReadError> {
+        let LinkHeader::Ethernet2(header) = &mut self.lp_header else {
+            return Ok(());
+        };
+        if !header.is_802_3() {
+            return Ok(());
+        }
+        let start = packet_reader.position;
+        match LlcHeader::new(packet_reader) {
+            Ok(llc) => {
+                header.llc = Some(llc);
+                Ok(())
+            }
+            Err(e) => {
+                packet_reader.position = start;
+                Err(e)
+            }
+        }
+    }
+
+    fn unknown_network(&mut self, ether_type: Option<u16>, error: ReadError, packet_reader: &mut PacketReader) {
+        self.network_header = Some(NetworkHeader::Unknown { ether_type, bytes: packet_reader.rest().to_vec() });
+        self.errors.push(error);
+    }
+
+    fn decode_transport(&mut self, protocol: u8, packet_reader: &mut PacketReader) {
+        let start = packet_reader.position;
+
+        match TransportHeader::new(protocol, packet_reader) {
+            Ok(tp_header @ TransportHeader::Unknown { .. }) => {
+                self.tp_header = Some(tp_header);
+            }
+            Ok(tp_header) => {
+                self.tp_header = Some(tp_header);
+                self.ap_header = ApplicationHeader::new(packet_reader).ok();
+            }
+            Err(e) => {
+                packet_reader.position = start;
+                self.tp_header = Some(TransportHeader::Unknown { protocol, bytes: packet_reader.rest().to_vec() });
+                self.errors.push(e);
+            }
+        }
+    }
+
+    pub fn ip_header(&self) -> Option<&IpHeader> {
+        self.network_header.as_ref()?.ip()
+    }
+
+    pub fn arp_header(&self) -> Option<&ArpHeader> {
+        self.network_header.as_ref()?.arp()
+    }
+
+    pub fn vlan_ids(&self) -> Vec<u16> {
+        self.lp_header.vlan_tags().iter().map(|tag| tag.vid).collect()
     }
 }
 
+fn ip_header(packet_reader: &mut PacketReader) -> Result<IpHeader, ReadError> {
+    IpHeader::new(packet_reader.peek(1)?[0] >> 4, packet_reader)
+}
+
 pub struct PacketReader<'a> {
     bytes: &'a [u8],
-    position: usize
+    position: usize,
+    // Layers are read up to here, the rest is the trailer.
+    end: usize
 }
 
 impl<'a> PacketReader<'a> {
     fn new(bytes: &[u8]) -> PacketReader {
         PacketReader {
             bytes,
-            position: 0
+            position: 0,
+            end: bytes.len()
         }
     }
 
+    fn limit(&mut self, end: usize) {
+        self.end = self.end.min(end).max(self.position);
+    }
+
+    // For headers that give the length of their payload. Anything after it,
+    // like padding, is left for the trailer.
+    pub fn limit_payload(&mut self, len: usize) {
+        self.limit(self.position + len);
+    }
+
     fn peek<'b>(&'b self, n: usize) -> Result<&'a [u8], ReadError> {
-        if self.bytes.len() < self.position + n {
-            return Err(ReadError::DataOffsetTooSmall(self.position + n - self.bytes.len()));
+        if self.end < self.position + n {
+            return Err(ReadError::DataOffsetTooSmall(self.position + n - self.end));
         }
 
         Ok(&self.bytes[self.position..self.position + n])
@@ -57,4 +225,110 @@ impl<'a> PacketReader<'a> {
         self.position += n;
         Ok(r)
     }
+
+    pub fn rest<'b>(&'b mut self) -> &'a [u8] {
+        let r = &self.bytes[self.position.min(self.end)..self.end];
+        self.position = self.end;
+        r
+    }
+
+    fn trailer<'b>(&'b mut self) -> &'a [u8] {
+        self.end = self.bytes.len();
+        self.rest()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::fixtures::{ethernet, ipv4, udp};
+    use crate::common::network::datalink::{network_layer, ETHERTYPE_IPV4, LINKTYPE_ETHERNET, LINKTYPE_RAW};
+    use crate::common::network::link::internet::transport::TransportHeader;
+    use crate::common::network::link::NetworkHeader;
+    use crate::common::network::packet::Packet;
+    use crate::common::network::ReadError;
+
+    #[test]
+    fn padding_after_ip_goes_to_trailer() {
+        let mut frame = ethernet(ETHERTYPE_IPV4, &ipv4(17, [10, 0, 0, 1], [10, 0, 0, 2], &udp(1, 2, b"hi")));
+        frame.resize(60, 0);
+        let packet = Packet::from_ethernet_bytes(&frame).unwrap();
+
+        assert!(matches!(packet.tp_header, Some(TransportHeader::UDP(_))));
+        assert!(packet.errors.is_empty());
+        assert_eq!(packet.trailer, [0; 16]);
+    }
+
+    #[test]
+    fn truncated_ip_header_is_unknown() {
+        let datagram = ipv4(17, [10, 0, 0, 1], [10, 0, 0, 2], &[]);
+        let frame = ethernet(ETHERTYPE_IPV4, &datagram[..12]);
+        let packet = Packet::from_ethernet_bytes(&frame).unwrap();
+
+        assert!(matches!(&packet.network_header, Some(NetworkHeader::Unknown { ether_type: Some(ETHERTYPE_IPV4), bytes }) if bytes.len() == 12));
+        assert!(matches!(packet.errors[..], [ReadError::DataOffsetTooSmall(_)]));
+        assert!(packet.trailer.is_empty());
+        assert_eq!(network_layer(LINKTYPE_ETHERNET, &frame), Some((ETHERTYPE_IPV4, 14)));
+    }
+
+    #[test]
+    fn wrong_ip_version_is_unknown() {
+        let mut datagram = ipv4(17, [10, 0, 0, 1], [10, 0, 0, 2], &[]);
+        datagram[0] = 0x55;
+        let packet = Packet::from_ethernet_bytes(&ethernet(ETHERTYPE_IPV4, &datagram)).unwrap();
+
+        assert!(matches!(&packet.network_header, Some(NetworkHeader::Unknown { ether_type: Some(ETHERTYPE_IPV4), bytes }) if bytes.len() == 20));
+        assert!(matches!(packet.errors[..], [ReadError::IPUnexpectedVersion(5)]));
+    }
+
+    #[test]
+    fn unsupported_ether_type_is_unknown() {
+        // LLDP.
+        let frame = ethernet(0x88CC, &[1, 2, 3]);
+        let packet = Packet::from_ethernet_bytes(&frame).unwrap();
+
+        assert!(matches!(&packet.network_header, Some(NetworkHeader::Unknown { ether_type: Some(0x88CC), bytes }) if bytes[..] == [1, 2, 3]));
+        assert!(matches!(packet.errors[..], [ReadError::UnsupportedEtherType(0x88CC)]));
+        assert_eq!(network_layer(LINKTYPE_ETHERNET, &frame), Some((0x88CC, 14)));
+    }
+
+    #[test]
+    fn truncated_transport_is_unknown_within_ip() {
+        // The IP header only covers half of the UDP header, the rest is trailer.
+        let mut datagram = ipv4(17, [10, 0, 0, 1], [10, 0, 0, 2], &udp(1, 2, &[]));
+        datagram[2..4].copy_from_slice(&24u16.to_be_bytes());
+        let packet = Packet::from_ethernet_bytes(&ethernet(ETHERTYPE_IPV4, &datagram)).unwrap();
+
+        assert!(packet.ip_header().is_some());
+        assert!(matches!(&packet.tp_header, Some(TransportHeader::Unknown { protocol: 17, bytes }) if bytes.len() == 4));
+        assert!(matches!(packet.errors[..], [ReadError::DataOffsetTooSmall(_)]));
+        assert_eq!(packet.trailer.len(), 4);
+    }
+
+    #[test]
+    fn unknown_transport_is_not_an_error() {
+        let datagram = ipv4(47, [10, 0, 0, 1], [10, 0, 0, 2], &[0, 0, 0x08, 0]);
+        let packet = Packet::from_ethernet_bytes(&ethernet(ETHERTYPE_IPV4, &datagram)).unwrap();
+
+        assert!(matches!(&packet.tp_header, Some(TransportHeader::Unknown { protocol: 47, bytes }) if bytes.len() == 4));
+        assert!(packet.errors.is_empty());
+    }
+
+    #[test]
+    fn raw_ip_is_guessed_from_version() {
+        let datagram = ipv4(17, [10, 0, 0, 1], [10, 0, 0, 2], &udp(1, 2, &[]));
+        let packet = Packet::from_bytes(LINKTYPE_RAW, &datagram).unwrap();
+        assert!(packet.tp_header.is_some());
+        assert_eq!(network_layer(LINKTYPE_RAW, &datagram), Some((ETHERTYPE_IPV4, 0)));
+
+        // Raw links promise IP, so anything else is an error.
+        let packet = Packet::from_bytes(LINKTYPE_RAW, &[0x00, 1, 2]).unwrap();
+        assert!(matches!(&packet.network_header, Some(NetworkHeader::Unknown { ether_type: None, bytes }) if bytes.len() == 3));
+        assert!(matches!(packet.errors[..], [ReadError::IPUnexpectedVersion(0)]));
+        assert_eq!(network_layer(LINKTYPE_RAW, &[0x00, 1, 2]), None);
+    }
+
+    #[test]
+    fn empty_frame_fails_at_link_layer() {
+        assert!(matches!(Packet::from_ethernet_bytes(&[]), Err(ReadError::DataOffsetTooSmall(14))));
+    }
 }
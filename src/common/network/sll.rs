@@ -0,0 +1,135 @@
+use serde::Serialize;
+use crate::common::network::packet::PacketReader;
+use crate::common::network::ReadError;
+
+// Linux "cooked" headers, used on the "any" device and on interfaces without
+// a link header of their own.
+#[derive(Serialize)]
+pub struct SllHeader {
+    pub packet_type: u16,
+    pub arphrd_type: u16,
+    pub address_length: u16,
+    pub address: [u8; 8],
+    pub protocol: u16
+}
+
+impl SllHeader {
+    const SIZE: usize = 16;
+
+    pub fn new<'a, 'b: 'a>(packet_reader: &'a mut PacketReader<'b>) -> Result<SllHeader, ReadError> {
+        let bytes = packet_reader.read(Self::SIZE)?;
+
+        Ok(SllHeader {
+            packet_type: u16::from_be_bytes(bytes[..2].try_into()?),
+            arphrd_type: u16::from_be_bytes(bytes[2..4].try_into()?),
+            address_length: u16::from_be_bytes(bytes[4..6].try_into()?),
+            address: bytes[6..14].try_into()?,
+            protocol: u16::from_be_bytes(bytes[14..16].try_into()?)
+        })
+    }
+}
+
+#[derive(Serialize)]
+pub struct Sll2Header {
+    pub protocol: u16,
+    pub interface_index: u32,
+    pub arphrd_type: u16,
+    pub packet_type: u8,
+    pub address_length: u8,
+    pub address: [u8; 8]
+}
+
+impl Sll2Header {
+    const SIZE: usize = 20;
+
+    pub fn new<'a, 'b: 'a>(packet_reader: &'a mut PacketReader<'b>) -> Result<Sll2Header, ReadError> {
+        let bytes = packet_reader.read(Self::SIZE)?;
+
+        // Bytes 2..4 are reserved.
+        Ok(Sll2Header {
+            protocol: u16::from_be_bytes(bytes[..2].try_into()?),
+            interface_index: u32::from_be_bytes(bytes[4..8].try_into()?),
+            arphrd_type: u16::from_be_bytes(bytes[8..10].try_into()?),
+            packet_type: bytes[10],
+            address_length: bytes[11],
+            address: bytes[12..20].try_into()?
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::fixtures::{ipv4, udp, MAC_A};
+    use crate::common::network::datalink::{network_layer, LinkHeader, ETHERTYPE_IPV4, LINKTYPE_LINUX_SLL, LINKTYPE_LINUX_SLL2};
+    use crate::common::network::link::NetworkHeader;
+    use crate::common::network::packet::Packet;
+    use crate::common::network::ReadError;
+
+    fn sll(protocol: u16, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0, 4, 0, 1, 0, 6];
+        frame.extend(MAC_A);
+        frame.extend([0, 0]);
+        frame.extend(protocol.to_be_bytes());
+        frame.extend(payload);
+        frame
+    }
+
+    fn sll2(protocol: u16, interface_index: u32, payload: &[u8]) -> Vec<u8> {
+        let mut frame = protocol.to_be_bytes().to_vec();
+        frame.extend([0, 0]);
+        frame.extend(interface_index.to_be_bytes());
+        frame.extend([0, 1, 4, 6]);
+        frame.extend(MAC_A);
+        frame.extend([0, 0]);
+        frame.extend(payload);
+        frame
+    }
+
+    fn datagram() -> Vec<u8> {
+        ipv4(17, [10, 0, 0, 1], [10, 0, 0, 2], &udp(1, 2, &[]))
+    }
+
+    #[test]
+    fn decodes_sll() {
+        let frame = sll(ETHERTYPE_IPV4, &datagram());
+        let packet = Packet::from_bytes(LINKTYPE_LINUX_SLL, &frame).unwrap();
+
+        let LinkHeader::LinuxSll(header) = &packet.lp_header else { panic!("not SLL") };
+        assert_eq!((header.packet_type, header.arphrd_type, header.address_length), (4, 1, 6));
+        assert_eq!(header.address[..6], MAC_A);
+        assert!(packet.tp_header.is_some());
+        assert_eq!(network_layer(LINKTYPE_LINUX_SLL, &frame), Some((ETHERTYPE_IPV4, 16)));
+    }
+
+    #[test]
+    fn decodes_sll2() {
+        let frame = sll2(ETHERTYPE_IPV4, 3, &datagram());
+        let packet = Packet::from_bytes(LINKTYPE_LINUX_SLL2, &frame).unwrap();
+
+        let LinkHeader::LinuxSll2(header) = &packet.lp_header else { panic!("not SLL2") };
+        assert_eq!((header.interface_index, header.arphrd_type, header.packet_type), (3, 1, 4));
+        assert!(packet.tp_header.is_some());
+        assert_eq!(network_layer(LINKTYPE_LINUX_SLL2, &frame), Some((ETHERTYPE_IPV4, 20)));
+    }
+
+    #[test]
+    fn keeps_unsupported_protocol_as_unknown() {
+        // ETH_P_802_2, which Linux uses for frames without an ethertype.
+        let frame = sll(0x0004, &[0x42, 0x42, 0x03]);
+        let packet = Packet::from_bytes(LINKTYPE_LINUX_SLL, &frame).unwrap();
+
+        assert!(matches!(&packet.network_header, Some(NetworkHeader::Unknown { ether_type: Some(0x0004), bytes }) if bytes.len() == 3));
+        assert!(matches!(packet.errors[..], [ReadError::UnsupportedEtherType(0x0004)]));
+        assert_eq!(network_layer(LINKTYPE_LINUX_SLL, &frame), Some((0x0004, 16)));
+    }
+
+    #[test]
+    fn rejects_truncated_headers() {
+        let frame = &sll(ETHERTYPE_IPV4, &[])[..15];
+        assert!(matches!(Packet::from_bytes(LINKTYPE_LINUX_SLL, frame), Err(ReadError::DataOffsetTooSmall(1))));
+        assert_eq!(network_layer(LINKTYPE_LINUX_SLL, frame), None);
+
+        let frame = &sll2(ETHERTYPE_IPV4, 3, &[])[..19];
+        assert!(matches!(Packet::from_bytes(LINKTYPE_LINUX_SLL2, frame), Err(ReadError::DataOffsetTooSmall(1))));
+    }
+}
@@ -1,51 +1,529 @@
-use std::net::{IpAddr, Ipv4Addr};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
-use pcap::{Capture, Device, Error};
+use pcap::{Capture, Device, Linktype, Precision, TimestampType};
 
-use crate::common::network::packet::Packet;
+use crate::common::pcap::captured::CapturedPacket;
+use crate::common::pcap::clock::{Clock, ReplaySpeed};
+use crate::common::pcap::device::{datalink, DeviceSelector};
+use crate::common::pcap::filter::FilterHandle;
+use crate::common::pcap::limits::CaptureLimits;
+use crate::common::pcap::options::{Backend, CaptureOptions};
+use crate::common::pcap::recovery::{InterfaceFault, LifecycleEvent, LifecycleHook, RecoveryPolicy};
+use crate::common::pcap::savefile::{Rotation, SavefileFormat, SavefileMode, SavefileOptions};
+use crate::common::pcap::session::{Packets, Session};
+use crate::common::pcap::source::{CaptureSource, SharedSource};
+use crate::common::pcap::stats::{Statistics, StatsHandle};
+use crate::common::pcap::stop::StopHandle;
 
+pub mod captured;
+pub mod clock;
+pub mod device;
+pub mod filter;
+pub mod interface;
+pub mod limits;
+pub mod options;
+pub mod pipeline;
+pub mod recovery;
+pub mod savefile;
+pub mod session;
+pub mod source;
+pub mod stats;
+pub mod stop;
+#[cfg(feature = "tokio")]
+pub mod stream;
+
+#[derive(Debug)]
+pub enum CaptureError {
+    NoMatchingDevice(DeviceSelector),
+    InvalidFilter(String, pcap::Error),
+    Savefile(std::io::Error),
+    Socket(std::io::Error),
+    InterfaceFault(InterfaceFault, String),
+    SourceConsumed,
+    Pcap(pcap::Error)
+}
+
+impl Display for CaptureError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CaptureError::NoMatchingDevice(selector) => {
+                write!(f, "No capture device matches {:?}.", selector)
+            }
+            CaptureError::InvalidFilter(expression, e) => {
+                write!(f, "Filter `{}` couldn't be compiled: {}.", expression, e)
+            }
+            CaptureError::Savefile(e) => {
+                write!(f, "Couldn't write savefile: {}.", e)
+            }
+            CaptureError::Socket(e) => {
+                write!(f, "Capture socket failed: {}.", e)
+            }
+            CaptureError::InterfaceFault(fault, message) => {
+                write!(f, "Capture stopped, {}: {}", fault, message)
+            }
+            CaptureError::SourceConsumed => {
+                write!(f, "The capture source has already been consumed.")
+            }
+            CaptureError::Pcap(e) => {
+                write!(f, "Capture failed: {}.", e)
+            }
+        }
+    }
+}
+
+impl Error for CaptureError {}
+
+impl From<pcap::Error> for CaptureError {
+    fn from(e: pcap::Error) -> Self {
+        CaptureError::Pcap(e)
+    }
+}
+
+impl From<std::io::Error> for CaptureError {
+    fn from(e: std::io::Error) -> Self {
+        CaptureError::Savefile(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Accept,
+    Reject,
+    // Keeps the current frame and ends the capture.
+    Stop
+}
+
+impl From<bool> for Verdict {
+    fn from(stop: bool) -> Self {
+        if stop { Verdict::Stop } else { Verdict::Accept }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Source {
+    Device(Device),
+    Devices(Vec<Device>),
+    File(PathBuf),
+    Custom(SharedSource)
+}
+
+#[derive(Clone)]
 pub struct Sniffer {
-    device: Device
+    source: Source,
+    options: CaptureOptions,
+    filter: FilterHandle,
+    savefile: Option<SavefileOptions>,
+    stats: StatsHandle,
+    stop: StopHandle,
+    clock: Clock,
+    recovery: RecoveryPolicy,
+    on_lifecycle: Option<LifecycleHook>,
+    limits: CaptureLimits
 }
 
 impl Sniffer {
-    pub fn new() -> Sniffer {
-        let device = {
-            let devices = Device::list().unwrap();
-            devices.iter().find(|device| {
-                device.addresses.iter().any(|addr| if let IpAddr::V4(addr) = addr.addr {
-                    addr == Ipv4Addr::new(192, 168, 178, 20)
-                } else { false })
-            }).unwrap().clone()
-        };
+    pub fn builder() -> SnifferBuilder {
+        SnifferBuilder::new()
+    }
+
+    pub fn source(&self) -> &Source {
+        &self.source
+    }
+
+    pub fn options(&self) -> &CaptureOptions {
+        &self.options
+    }
+
+    pub fn filter(&self) -> &FilterHandle {
+        &self.filter
+    }
+
+    pub fn stats(&self) -> Statistics {
+        self.stats.snapshot()
+    }
 
-        Sniffer {
-            device
+    pub fn stats_handle(&self) -> StatsHandle {
+        self.stats.clone()
+    }
+
+    pub fn stop_handle(&self) -> StopHandle {
+        self.stop.clone()
+    }
+
+    pub fn clock(&self) -> Clock {
+        self.clock.clone()
+    }
+
+    pub fn sniff<V: Into<Verdict>>(&self, mut f: impl FnMut(CapturedPacket) -> V) -> Result<Statistics, CaptureError> {
+        let mut session = Session::open(self)?;
+
+        while let Some(frame) = session.next_frame()? {
+            let verdict = session.decode(&frame).map(|packet| f(packet).into());
+            session.record(&frame, verdict)?;
+
+            if let Some(Verdict::Stop) = verdict {
+                break;
+            }
         }
+        session.finish()
+    }
+
+    pub fn packets(&self) -> Result<Packets<'_>, CaptureError> {
+        Ok(Packets::new(Session::open(self)?))
+    }
+}
+
+pub struct SnifferBuilder {
+    device: DeviceSelector,
+    devices: Vec<DeviceSelector>,
+    file: Option<PathBuf>,
+    custom: Option<SharedSource>,
+    options: CaptureOptions,
+    filter: Option<String>,
+    savefile: Option<PathBuf>,
+    // Kept apart from the path so they can be set in any order.
+    savefile_format: Option<SavefileFormat>,
+    savefile_mode: Option<SavefileMode>,
+    savefile_rotation: Option<Rotation>,
+    recovery: RecoveryPolicy,
+    on_lifecycle: Option<LifecycleHook>,
+    limits: CaptureLimits
+}
+
+impl SnifferBuilder {
+    pub fn new() -> SnifferBuilder {
+        SnifferBuilder {
+            device: DeviceSelector::DefaultRoute,
+            devices: vec![],
+            file: None,
+            custom: None,
+            options: CaptureOptions::default(),
+            filter: None,
+            savefile: None,
+            savefile_format: None,
+            savefile_mode: None,
+            savefile_rotation: None,
+            recovery: RecoveryPolicy::default(),
+            on_lifecycle: None,
+            limits: CaptureLimits::default()
+        }
+    }
+
+    pub fn device(mut self, selector: DeviceSelector) -> SnifferBuilder {
+        self.device = selector;
+        self.devices.clear();
+        self.file = None;
+        self.custom = None;
+        self
+    }
+
+    // Captures on every selected device at once, merging their frames by timestamp.
+    pub fn devices(mut self, selectors: impl IntoIterator<Item = DeviceSelector>) -> SnifferBuilder {
+        self.devices = selectors.into_iter().collect();
+        self.file = None;
+        self.custom = None;
+        self
+    }
+
+    pub fn device_name(self, name: impl Into<String>) -> SnifferBuilder {
+        self.device(DeviceSelector::Name(name.into()))
+    }
+
+    pub fn device_address(self, addr: impl Into<IpAddr>) -> SnifferBuilder {
+        self.device(DeviceSelector::Address(addr.into()))
+    }
+
+    pub fn default_route(self) -> SnifferBuilder {
+        self.device(DeviceSelector::DefaultRoute)
+    }
+
+    pub fn first_up(self) -> SnifferBuilder {
+        self.device(DeviceSelector::FirstUp)
+    }
+
+    pub fn file(mut self, path: impl Into<PathBuf>) -> SnifferBuilder {
+        self.file = Some(path.into());
+        self.devices.clear();
+        self.custom = None;
+        self
+    }
+
+    pub fn source(mut self, source: impl CaptureSource + 'static) -> SnifferBuilder {
+        self.custom = Some(SharedSource::new(Box::new(source)));
+        self.devices.clear();
+        self.file = None;
+        self
+    }
+
+    pub fn options(mut self, options: CaptureOptions) -> SnifferBuilder {
+        self.options = options;
+        self
+    }
+
+    pub fn snaplen(mut self, snaplen: u32) -> SnifferBuilder {
+        self.options.snaplen = snaplen;
+        self
+    }
+
+    pub fn promiscuous(mut self, promiscuous: bool) -> SnifferBuilder {
+        self.options.promiscuous = promiscuous;
+        self
+    }
+
+    pub fn buffer_size(mut self, bytes: u32) -> SnifferBuilder {
+        self.options.buffer_size = Some(bytes);
+        self
+    }
+
+    pub fn immediate_mode(mut self, immediate_mode: bool) -> SnifferBuilder {
+        self.options.immediate_mode = immediate_mode;
+        self
+    }
+
+    pub fn timestamp_type(mut self, timestamp_type: TimestampType) -> SnifferBuilder {
+        self.options.timestamp_type = Some(timestamp_type);
+        self
+    }
+
+    pub fn backend(mut self, backend: Backend) -> SnifferBuilder {
+        self.options.backend = backend;
+        self
+    }
+
+    pub fn limits(mut self, limits: CaptureLimits) -> SnifferBuilder {
+        self.limits = limits;
+        self
+    }
+
+    pub fn max_packets(mut self, max_packets: u64) -> SnifferBuilder {
+        self.limits.max_packets = Some(max_packets);
+        self
+    }
+
+    pub fn max_bytes(mut self, max_bytes: u64) -> SnifferBuilder {
+        self.limits.max_bytes = Some(max_bytes);
+        self
+    }
+
+    pub fn max_duration(mut self, max_duration: Duration) -> SnifferBuilder {
+        self.limits.max_duration = Some(max_duration);
+        self
+    }
+
+    pub fn max_capture_time(mut self, max_capture_time: Duration) -> SnifferBuilder {
+        self.limits.max_capture_time = Some(max_capture_time);
+        self
     }
 
-    pub fn sniff(&self, f: impl Fn(Packet) -> bool) {
-        let mut cap = Capture::from_device(self.device.clone()).unwrap()
-            .timeout(0).open().unwrap();
-        //cap.filter("internet and udp", false);
-
-        loop {
-            match cap.next_packet() {
-                Ok(packet) => {
-                    let frame = Packet::from_ethernet_bytes(packet.data);
-                    if let Ok(frame) = frame {
-                        if f(frame) {
-                            break;
-                        }
-                    }
-                }
-                Err(e) => {
-                    if let Error::TimeoutExpired = e {
-                    } else {
-                        println!("{:?}", e);
-                    }
-                }
+    pub fn recovery(mut self, policy: RecoveryPolicy) -> SnifferBuilder {
+        self.recovery = policy;
+        self
+    }
+
+    pub fn on_lifecycle(mut self, f: impl Fn(&LifecycleEvent) + Send + Sync + 'static) -> SnifferBuilder {
+        self.on_lifecycle = Some(Arc::new(f));
+        self
+    }
+
+    pub fn replay(mut self, speed: ReplaySpeed) -> SnifferBuilder {
+        self.options.replay = Some(speed);
+        self
+    }
+
+    pub fn nanosecond_precision(mut self, nanosecond: bool) -> SnifferBuilder {
+        self.options.precision = if nanosecond { Precision::Nano } else { Precision::Micro };
+        self
+    }
+
+    pub fn filter(mut self, expression: impl Into<String>) -> SnifferBuilder {
+        self.filter = Some(expression.into());
+        self
+    }
+
+    pub fn savefile(mut self, path: impl Into<PathBuf>) -> SnifferBuilder {
+        self.savefile = Some(path.into());
+        self
+    }
+
+    pub fn savefile_format(mut self, format: SavefileFormat) -> SnifferBuilder {
+        self.savefile_format = Some(format);
+        self
+    }
+
+    pub fn savefile_mode(mut self, mode: SavefileMode) -> SnifferBuilder {
+        self.savefile_mode = Some(mode);
+        self
+    }
+
+    pub fn savefile_rotation(mut self, rotation: Rotation) -> SnifferBuilder {
+        self.savefile_rotation = Some(rotation);
+        self
+    }
+
+    pub fn build(self) -> Result<Sniffer, CaptureError> {
+        let (source, link_types) = match (self.custom, self.file) {
+            (Some(custom), _) => {
+                let link_type = Linktype(i32::from(custom.link_type()));
+                (Source::Custom(custom), vec![link_type])
+            }
+            (None, Some(path)) => {
+                let link_type = Capture::from_file(&path)?.get_datalink();
+                (Source::File(path), vec![link_type])
             }
+            (None, None) if !self.devices.is_empty() => {
+                let devices: Vec<Device> = self.devices.iter().map(DeviceSelector::select).collect::<Result<_, _>>()?;
+                let link_types = devices.iter().map(datalink).collect();
+                (Source::Devices(devices), link_types)
+            }
+            (None, None) => {
+                let device = self.device.select()?;
+                let link_types = vec![datalink(&device)];
+                (Source::Device(device), link_types)
+            }
+        };
+
+        let savefile = self.savefile.map(|path| {
+            let mut savefile = SavefileOptions::new(path);
+            savefile.format = self.savefile_format.unwrap_or(savefile.format);
+            savefile.mode = self.savefile_mode.unwrap_or(savefile.mode);
+            savefile.rotation = self.savefile_rotation;
+            savefile
+        });
+
+        let clock = match self.options.replay {
+            Some(speed) => Clock::simulated(speed),
+            None => Clock::wall()
+        };
+
+        Ok(Sniffer {
+            source,
+            options: self.options,
+            filter: FilterHandle::new(self.filter, link_types)?,
+            savefile,
+            stats: StatsHandle::default(),
+            stop: StopHandle::default(),
+            clock,
+            recovery: self.recovery,
+            on_lifecycle: self.on_lifecycle,
+            limits: self.limits
+        })
+    }
+}
+
+impl Default for SnifferBuilder {
+    fn default() -> Self {
+        SnifferBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::common::fixtures::ethernet_udp;
+    use crate::common::network::link::internet::transport::TransportHeader;
+    use crate::common::pcap::captured::CapturedPacket;
+    use crate::common::pcap::limits::Limit;
+    use crate::common::pcap::savefile::SavefileMode;
+    use crate::common::pcap::source::{Frame, MemorySource};
+    use crate::common::pcap::{Sniffer, Verdict};
+    use crate::common::pcapng::PcapNgReader;
+
+    fn udp_frame(dst_port: u16, payload: &[u8]) -> Vec<u8> {
+        ethernet_udp([10, 0, 0, 1], [10, 0, 0, 2], 40000, dst_port, payload)
+    }
+
+    fn dst_port(packet: &CapturedPacket) -> Option<u16> {
+        match &packet.packet.tp_header {
+            Some(TransportHeader::UDP(header)) => Some(header.dst_port),
+            _ => None
         }
     }
-}
\ No newline at end of file
+
+    fn sniffer(frames: Vec<Vec<u8>>) -> Sniffer {
+        Sniffer::builder().source(MemorySource::new(1, frames)).build().unwrap()
+    }
+
+    #[test]
+    fn filters_offline_sources() {
+        let frames = vec![udp_frame(53, &[]), udp_frame(80, &[]), udp_frame(53, &[])];
+        let sniffer = Sniffer::builder().source(MemorySource::new(1, frames)).filter("udp port 53").build().unwrap();
+
+        let mut ports = vec![];
+        let stats = sniffer.sniff(|packet| {
+            ports.push(dst_port(&packet));
+            false
+        }).unwrap();
+        assert_eq!(ports, [Some(53), Some(53)]);
+        assert_eq!(stats.captured, 2);
+    }
+
+    #[test]
+    fn counts_verdicts_and_decode_errors() {
+        let frames = vec![udp_frame(53, &[]), vec![1, 2, 3], udp_frame(80, &[]), udp_frame(53, &[])];
+        let stats = sniffer(frames).sniff(|packet| {
+            if dst_port(&packet) == Some(80) { Verdict::Reject } else { Verdict::Accept }
+        }).unwrap();
+
+        assert_eq!((stats.captured, stats.decoded, stats.rejected), (4, 3, 1));
+        assert_eq!(stats.decode_failures(), 1);
+        assert_eq!(stats.decode_errors.get("DataOffsetTooSmall"), Some(&1));
+    }
+
+    #[test]
+    fn stops_at_packet_and_byte_limits() {
+        let frames = vec![udp_frame(53, &[]); 5];
+        let len = frames[0].len() as u64;
+
+        let sniffer = Sniffer::builder().source(MemorySource::new(1, frames.clone())).max_packets(2).build().unwrap();
+        let stats = sniffer.sniff(|_| false).unwrap();
+        assert_eq!((stats.captured, stats.limit_reached), (2, Some(Limit::Packets)));
+
+        // The frame that crosses the limit is still delivered.
+        let sniffer = Sniffer::builder().source(MemorySource::new(1, frames)).max_bytes(len + 1).build().unwrap();
+        let stats = sniffer.sniff(|_| false).unwrap();
+        assert_eq!((stats.captured, stats.bytes, stats.limit_reached), (2, 2 * len, Some(Limit::Bytes)));
+    }
+
+    #[test]
+    fn stops_at_capture_time() {
+        let frames = (0..4).map(|second| Frame::new(1, Duration::from_secs(second), udp_frame(53, &[]))).collect();
+        let sniffer = Sniffer::builder()
+            .source(MemorySource::from_frames(1, frames))
+            .max_capture_time(Duration::from_millis(1500))
+            .build()
+            .unwrap();
+
+        let stats = sniffer.sniff(|_| false).unwrap();
+        assert_eq!((stats.captured, stats.limit_reached), (2, Some(Limit::CaptureTime)));
+    }
+
+    #[test]
+    fn accepted_savefile_keeps_accepted_and_stopping_frames() {
+        let path = std::env::temp_dir().join(format!("network-sniffer-accepted-{}.pcapng", std::process::id()));
+        let frames = vec![udp_frame(53, b"a"), udp_frame(80, b"b"), vec![1, 2, 3], udp_frame(443, b"c"), udp_frame(53, b"d")];
+        let sniffer = Sniffer::builder()
+            .source(MemorySource::new(1, frames.clone()))
+            .savefile(&path)
+            .savefile_mode(SavefileMode::Accepted)
+            .build()
+            .unwrap();
+
+        sniffer.sniff(|packet| {
+            match dst_port(&packet) {
+                Some(80) => Verdict::Reject,
+                Some(443) => Verdict::Stop,
+                _ => Verdict::Accept
+            }
+        }).unwrap();
+
+        let saved: Vec<Vec<u8>> = PcapNgReader::from_file(&path).unwrap().map(|packet| packet.unwrap().data).collect();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(saved, [frames[0].clone(), frames[3].clone()]);
+    }
+}
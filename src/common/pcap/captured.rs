@@ -0,0 +1,38 @@
+use std::ops::Deref;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::common::network::packet::Packet;
+
+#[derive(Serialize)]
+pub struct CapturedPacket {
+    pub timestamp: Duration,
+    pub caplen: u32,
+    pub len: u32,
+    pub interface: u32,
+    pub link_type: u16,
+    pub packet: Packet
+}
+
+impl CapturedPacket {
+    pub fn timestamp_nanos(&self) -> u128 {
+        self.timestamp.as_nanos()
+    }
+
+    pub fn is_truncated(&self) -> bool {
+        self.caplen < self.len
+    }
+
+    pub fn into_packet(self) -> Packet {
+        self.packet
+    }
+}
+
+impl Deref for CapturedPacket {
+    type Target = Packet;
+
+    fn deref(&self) -> &Packet {
+        &self.packet
+    }
+}
@@ -0,0 +1,81 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplaySpeed {
+    AsFastAsPossible,
+    // 1.0 is the original pace, 2.0 twice as fast.
+    Scaled(f64)
+}
+
+impl ReplaySpeed {
+    pub const ORIGINAL: ReplaySpeed = ReplaySpeed::Scaled(1.0);
+
+    // Speeds that aren't positive and finite don't pace at all.
+    pub fn factor(&self) -> Option<f64> {
+        match *self {
+            ReplaySpeed::Scaled(speed) if speed.is_finite() && speed > 0.0 => Some(speed),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug)]
+enum ClockState {
+    Wall,
+    Simulated {
+        speed: ReplaySpeed,
+        // Timestamp of the latest frame and when it was delivered.
+        last: Option<(Duration, Instant)>
+    }
+}
+
+// Time as seen by the capture, in the same terms as frame timestamps. It is
+// the wall clock for live captures and follows the frames during a replay,
+// so timeouts measured against it behave as they did when capturing.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    state: Arc<Mutex<ClockState>>
+}
+
+impl Clock {
+    pub fn wall() -> Clock {
+        Clock {
+            state: Arc::new(Mutex::new(ClockState::Wall))
+        }
+    }
+
+    pub fn simulated(speed: ReplaySpeed) -> Clock {
+        Clock {
+            state: Arc::new(Mutex::new(ClockState::Simulated { speed, last: None }))
+        }
+    }
+
+    pub fn is_simulated(&self) -> bool {
+        matches!(*self.state.lock().unwrap(), ClockState::Simulated { .. })
+    }
+
+    // Before the first frame of a replay there is no time yet, so this is zero.
+    pub fn now(&self) -> Duration {
+        match &*self.state.lock().unwrap() {
+            ClockState::Wall => SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default(),
+            ClockState::Simulated { last: None, .. } => Duration::ZERO,
+            ClockState::Simulated { speed, last: Some((timestamp, delivered)) } => match speed.factor() {
+                Some(speed) => *timestamp + delivered.elapsed().mul_f64(speed),
+                None => *timestamp
+            }
+        }
+    }
+
+    pub(in crate::common::pcap) fn advance(&self, timestamp: Duration) {
+        if let ClockState::Simulated { last, .. } = &mut *self.state.lock().unwrap() {
+            *last = Some((timestamp, Instant::now()));
+        }
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Clock::wall()
+    }
+}
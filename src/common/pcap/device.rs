@@ -0,0 +1,72 @@
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+
+use pcap::{Capture, Device, Linktype};
+
+use crate::common::pcap::CaptureError;
+
+#[derive(Debug, Clone)]
+pub enum DeviceSelector {
+    Name(String),
+    Address(IpAddr),
+    DefaultRoute,
+    FirstUp
+}
+
+impl DeviceSelector {
+    pub fn select(&self) -> Result<Device, CaptureError> {
+        let devices = Device::list()?;
+
+        let device = match self {
+            DeviceSelector::Name(name) => {
+                devices.into_iter().find(|device| &device.name == name)
+            }
+            DeviceSelector::Address(addr) => {
+                devices.into_iter().find(|device| has_address(device, addr))
+            }
+            DeviceSelector::DefaultRoute => {
+                default_route_addresses().iter().find_map(|addr| {
+                    devices.iter().find(|device| has_address(device, addr)).cloned()
+                })
+            }
+            DeviceSelector::FirstUp => {
+                devices.into_iter().find(|device| device.flags.is_up() && !device.flags.is_loopback())
+            }
+        };
+
+        device.ok_or_else(|| CaptureError::NoMatchingDevice(self.clone()))
+    }
+}
+
+// The datalink a live capture on `device` reports. Asking libpcap means
+// opening the device, so without capture privileges it is guessed instead.
+pub(in crate::common::pcap) fn datalink(device: &Device) -> Linktype {
+    let opened = Capture::from_device(device.clone())
+        .and_then(|cap| cap.open())
+        .map(|cap| cap.get_datalink());
+
+    match opened {
+        Ok(link_type) => link_type,
+        Err(_) if device.name == "any" => Linktype::LINUX_SLL,
+        Err(_) if device.flags.is_loopback() && !cfg!(target_os = "linux") => Linktype::NULL,
+        Err(_) => Linktype::ETHERNET
+    }
+}
+
+fn has_address(device: &Device, addr: &IpAddr) -> bool {
+    device.addresses.iter().any(|address| &address.addr == addr)
+}
+
+// Connecting a UDP socket doesn't send anything, it only makes the OS pick the
+// outgoing interface, whose address is then reported as the local address.
+fn default_route_addresses() -> Vec<IpAddr> {
+    let probes: [(&str, &str); 2] = [
+        ("0.0.0.0:0", "8.8.8.8:53"),
+        ("[::]:0", "[2001:4860:4860::8888]:53")
+    ];
+
+    probes.iter().filter_map(|(local, remote)| {
+        let socket = UdpSocket::bind(local).ok()?;
+        socket.connect(remote.parse::<SocketAddr>().ok()?).ok()?;
+        Some(socket.local_addr().ok()?.ip())
+    }).collect()
+}
@@ -0,0 +1,93 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use pcap::{Capture, Linktype};
+
+use crate::common::pcap::source::CaptureSource;
+use crate::common::pcap::CaptureError;
+
+#[derive(Clone)]
+pub struct FilterHandle {
+    shared: Arc<SharedFilter>
+}
+
+struct SharedFilter {
+    state: Mutex<FilterState>,
+    changed: AtomicBool
+}
+
+// Merged captures may mix link types, and a filter has to compile for each.
+struct FilterState {
+    expression: Option<String>,
+    link_types: Vec<Linktype>
+}
+
+impl FilterHandle {
+    pub(in crate::common::pcap) fn new(expression: Option<String>, link_types: Vec<Linktype>) -> Result<FilterHandle, CaptureError> {
+        if let Some(expression) = &expression {
+            compile_all(expression, &link_types)?;
+        }
+
+        Ok(FilterHandle {
+            shared: Arc::new(SharedFilter {
+                state: Mutex::new(FilterState {
+                    expression,
+                    link_types
+                }),
+                changed: AtomicBool::new(false)
+            })
+        })
+    }
+
+    pub fn expression(&self) -> Option<String> {
+        self.shared.state.lock().unwrap().expression.clone()
+    }
+
+    pub fn set(&self, expression: impl Into<String>) -> Result<(), CaptureError> {
+        let expression = expression.into();
+        let mut state = self.shared.state.lock().unwrap();
+        compile_all(&expression, &state.link_types)?;
+
+        state.expression = Some(expression);
+        self.shared.changed.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    pub fn clear(&self) {
+        self.shared.state.lock().unwrap().expression = None;
+        self.shared.changed.store(true, Ordering::Release);
+    }
+
+    pub(in crate::common::pcap) fn apply(&self, source: &mut dyn CaptureSource) -> Result<(), CaptureError> {
+        let mut state = self.shared.state.lock().unwrap();
+        state.link_types = source.interfaces().iter().map(|interface| Linktype(i32::from(interface.link_type))).collect();
+        self.shared.changed.store(false, Ordering::Release);
+
+        // An empty program accepts every packet, which is how a filter is removed.
+        source.set_filter(state.expression.as_deref().unwrap_or(""))
+    }
+
+    pub(in crate::common::pcap) fn apply_if_changed(&self, source: &mut dyn CaptureSource) -> Result<(), CaptureError> {
+        if self.shared.changed.load(Ordering::Acquire) {
+            self.apply(source)?;
+        }
+        Ok(())
+    }
+}
+
+fn compile_all(expression: &str, link_types: &[Linktype]) -> Result<(), CaptureError> {
+    let mut checked = vec![];
+    for link_type in link_types {
+        if !checked.contains(&link_type.0) {
+            compile(expression, *link_type)?;
+            checked.push(link_type.0);
+        }
+    }
+    Ok(())
+}
+
+pub fn compile(expression: &str, link_type: Linktype) -> Result<(), CaptureError> {
+    Capture::dead(link_type)?.compile(expression, true)
+        .map(|_| ())
+        .map_err(|e| CaptureError::InvalidFilter(expression.to_string(), e))
+}
@@ -0,0 +1,183 @@
+#[cfg(target_os = "windows")]
+use std::mem;
+use std::net::IpAddr;
+
+use pcap::{Capture, ConnectionStatus, Device};
+use serde::Serialize;
+#[cfg(target_os = "windows")]
+use windows::Win32::Foundation::ERROR_BUFFER_OVERFLOW;
+#[cfg(target_os = "windows")]
+use windows::Win32::NetworkManagement::IpHelper::{GetAdaptersAddresses, GAA_FLAG_SKIP_ANYCAST, GAA_FLAG_SKIP_DNS_SERVER, GAA_FLAG_SKIP_FRIENDLY_NAME, GAA_FLAG_SKIP_MULTICAST, GAA_FLAG_SKIP_UNICAST, IP_ADAPTER_ADDRESSES_LH};
+#[cfg(target_os = "windows")]
+use windows::Win32::Networking::WinSock::AF_UNSPEC;
+
+use crate::common::pcap::device::DeviceSelector;
+use crate::common::pcap::CaptureError;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Interface {
+    pub name: String,
+    pub description: Option<String>,
+    pub flags: InterfaceFlags,
+    pub addresses: Vec<InterfaceAddress>,
+    pub mac: Option<[u8; 6]>,
+    pub mtu: Option<u32>,
+    pub datalinks: Vec<Datalink>
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct InterfaceFlags {
+    pub up: bool,
+    pub running: bool,
+    pub loopback: bool,
+    pub wireless: bool,
+    // None when unknown or when the notion doesn't apply, as for loopback.
+    pub connected: Option<bool>
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InterfaceAddress {
+    pub addr: IpAddr,
+    pub netmask: Option<IpAddr>,
+    pub broadcast: Option<IpAddr>,
+    pub destination: Option<IpAddr>
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Datalink {
+    pub link_type: u16,
+    pub name: Option<String>,
+    pub description: Option<String>
+}
+
+impl Interface {
+    pub fn list() -> Result<Vec<Interface>, CaptureError> {
+        Ok(Device::list()?.iter().map(Interface::from_device).collect())
+    }
+
+    pub fn find(name: &str) -> Result<Option<Interface>, CaptureError> {
+        Ok(Device::list()?.iter().find(|device| device.name == name).map(Interface::from_device))
+    }
+
+    pub fn selector(&self) -> DeviceSelector {
+        DeviceSelector::Name(self.name.clone())
+    }
+
+    fn from_device(device: &Device) -> Interface {
+        let flags = &device.flags;
+
+        Interface {
+            name: device.name.clone(),
+            description: device.desc.clone(),
+            flags: InterfaceFlags {
+                up: flags.is_up(),
+                running: flags.is_running(),
+                loopback: flags.is_loopback(),
+                wireless: flags.is_wireless(),
+                connected: match flags.connection_status {
+                    ConnectionStatus::Connected => Some(true),
+                    ConnectionStatus::Disconnected => Some(false),
+                    ConnectionStatus::Unknown | ConnectionStatus::NotApplicable => None
+                }
+            },
+            addresses: device.addresses.iter().map(|address| InterfaceAddress {
+                addr: address.addr,
+                netmask: address.netmask,
+                broadcast: address.broadcast_addr,
+                destination: address.dst_addr
+            }).collect(),
+            mac: mac_address(&device.name),
+            mtu: mtu(&device.name),
+            datalinks: datalinks(device)
+        }
+    }
+}
+
+// Listing datalinks means opening the device, which usually needs capture
+// privileges; without them the list is left empty.
+fn datalinks(device: &Device) -> Vec<Datalink> {
+    let links = Capture::from_device(device.clone())
+        .and_then(|cap| cap.open())
+        .and_then(|cap| cap.list_datalinks());
+
+    links.unwrap_or_default().into_iter().map(|link| Datalink {
+        link_type: link.0 as u16,
+        name: link.get_name().ok(),
+        description: link.get_description().ok()
+    }).collect()
+}
+
+#[cfg(target_os = "linux")]
+fn sysfs(name: &str, attribute: &str) -> Option<String> {
+    let value = std::fs::read_to_string(format!("/sys/class/net/{}/{}", name, attribute)).ok()?;
+    Some(value.trim().to_string())
+}
+
+#[cfg(target_os = "linux")]
+fn mac_address(name: &str) -> Option<[u8; 6]> {
+    let octets = sysfs(name, "address")?
+        .split(':')
+        .map(|octet| u8::from_str_radix(octet, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    octets.try_into().ok()
+}
+
+#[cfg(target_os = "linux")]
+fn mtu(name: &str) -> Option<u32> {
+    sysfs(name, "mtu")?.parse().ok()
+}
+
+// Npcap names devices `\Device\NPF_{GUID}`, and the GUID is what IP Helper
+// calls the adapter name.
+#[cfg(target_os = "windows")]
+fn adapter<T>(name: &str, f: impl FnOnce(&IP_ADAPTER_ADDRESSES_LH) -> Option<T>) -> Option<T> {
+    let guid = name.strip_prefix(r"\Device\NPF_")?;
+    let flags = GAA_FLAG_SKIP_UNICAST | GAA_FLAG_SKIP_ANYCAST | GAA_FLAG_SKIP_MULTICAST | GAA_FLAG_SKIP_DNS_SERVER | GAA_FLAG_SKIP_FRIENDLY_NAME;
+
+    // The list can grow between asking for its size and reading it, so the
+    // call is retried a few times with the size it asks for.
+    let mut size: u32 = 16 * 1024;
+    let mut buffer: Vec<u64> = vec![];
+    for _ in 0..3 {
+        buffer.resize(size as usize / mem::size_of::<u64>() + 1, 0);
+        let adapters = buffer.as_mut_ptr() as *mut IP_ADAPTER_ADDRESSES_LH;
+        match unsafe { GetAdaptersAddresses(AF_UNSPEC, flags, None, Some(adapters), &mut size) } {
+            0 => break,
+            error if error == ERROR_BUFFER_OVERFLOW.0 => continue,
+            _ => return None
+        }
+    }
+
+    let mut adapter = buffer.as_ptr() as *const IP_ADAPTER_ADDRESSES_LH;
+    while !adapter.is_null() {
+        let current = unsafe { &*adapter };
+        if unsafe { current.AdapterName.as_bytes() }.eq_ignore_ascii_case(guid.as_bytes()) {
+            return f(current);
+        }
+        adapter = current.Next;
+    }
+    None
+}
+
+#[cfg(target_os = "windows")]
+fn mac_address(name: &str) -> Option<[u8; 6]> {
+    adapter(name, |adapter| match adapter.PhysicalAddressLength {
+        6 => adapter.PhysicalAddress[..6].try_into().ok(),
+        _ => None
+    })
+}
+
+#[cfg(target_os = "windows")]
+fn mtu(name: &str) -> Option<u32> {
+    adapter(name, |adapter| Some(adapter.Mtu))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+fn mac_address(_name: &str) -> Option<[u8; 6]> {
+    None
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+fn mtu(_name: &str) -> Option<u32> {
+    None
+}
@@ -0,0 +1,41 @@
+use std::time::Duration;
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Limit {
+    Packets,
+    Bytes,
+    Duration,
+    CaptureTime
+}
+
+// Packets and bytes count captured frames, before decoding and the callback.
+// `max_duration` is wall-clock time since the capture opened, while
+// `max_capture_time` is measured between frame timestamps, which is what
+// matters when reading or replaying savefiles.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CaptureLimits {
+    pub max_packets: Option<u64>,
+    pub max_bytes: Option<u64>,
+    pub max_duration: Option<Duration>,
+    pub max_capture_time: Option<Duration>
+}
+
+impl CaptureLimits {
+    pub(in crate::common::pcap) fn reached(&self, packets: u64, bytes: u64, elapsed: Duration) -> Option<Limit> {
+        if self.max_packets.is_some_and(|max| packets >= max) {
+            Some(Limit::Packets)
+        } else if self.max_bytes.is_some_and(|max| bytes >= max) {
+            Some(Limit::Bytes)
+        } else if self.max_duration.is_some_and(|max| elapsed >= max) {
+            Some(Limit::Duration)
+        } else {
+            None
+        }
+    }
+
+    pub(in crate::common::pcap) fn past_capture_time(&self, first: Duration, timestamp: Duration) -> bool {
+        self.max_capture_time.is_some_and(|max| timestamp.saturating_sub(first) > max)
+    }
+}
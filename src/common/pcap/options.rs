@@ -0,0 +1,70 @@
+use pcap::{Active, Capture, Device, Inactive, Precision, TimestampType};
+
+use crate::common::pcap::clock::ReplaySpeed;
+use crate::common::pcap::CaptureError;
+#[cfg(target_os = "linux")]
+use crate::common::pcap::source::af_packet::AfPacketOptions;
+
+pub const DEFAULT_SNAPLEN: u32 = 262144;
+
+#[derive(Debug, Clone)]
+pub enum Backend {
+    Pcap,
+    // A memory-mapped ring read without a syscall per packet. Frames are
+    // still copied out of the ring, see `AfPacketSource`.
+    #[cfg(target_os = "linux")]
+    AfPacket(AfPacketOptions)
+}
+
+#[derive(Debug, Clone)]
+pub struct CaptureOptions {
+    pub backend: Backend,
+    pub snaplen: u32,
+    pub promiscuous: bool,
+    pub buffer_size: Option<u32>,
+    pub immediate_mode: bool,
+    pub timestamp_type: Option<TimestampType>,
+    pub precision: Precision,
+    pub timeout_ms: i32,
+    // Paces frames from savefiles and injected sources like the original capture.
+    pub replay: Option<ReplaySpeed>
+}
+
+impl CaptureOptions {
+    pub fn is_nanosecond(&self) -> bool {
+        self.precision == Precision::Nano
+    }
+
+    pub(in crate::common::pcap) fn open(&self, device: Device) -> Result<Capture<Active>, CaptureError> {
+        let mut cap: Capture<Inactive> = Capture::from_device(device)?
+            .timeout(self.timeout_ms)
+            .snaplen(self.snaplen.min(i32::MAX as u32) as i32)
+            .promisc(self.promiscuous)
+            .immediate_mode(self.immediate_mode)
+            .precision(self.precision);
+
+        if let Some(buffer_size) = self.buffer_size {
+            cap = cap.buffer_size(buffer_size.min(i32::MAX as u32) as i32);
+        }
+        if let Some(timestamp_type) = self.timestamp_type {
+            cap = cap.tstamp_type(timestamp_type);
+        }
+        Ok(cap.open()?)
+    }
+}
+
+impl Default for CaptureOptions {
+    fn default() -> Self {
+        CaptureOptions {
+            backend: Backend::Pcap,
+            snaplen: DEFAULT_SNAPLEN,
+            promiscuous: false,
+            buffer_size: None,
+            immediate_mode: false,
+            timestamp_type: None,
+            precision: Precision::Micro,
+            timeout_ms: 100,
+            replay: None
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::mem;
+use std::panic;
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::Arc;
+use std::thread;
+
+use crate::common::network::datalink::{network_layer, ETHERTYPE_IPV4, ETHERTYPE_IPV6};
+use crate::common::pcap::captured::CapturedPacket;
+use crate::common::pcap::session::Session;
+use crate::common::pcap::source::Frame;
+use crate::common::pcap::stats::{QueueCounters, Statistics};
+use crate::common::pcap::{CaptureError, Sniffer, Verdict};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropPolicy {
+    Block,
+    DropNewest
+}
+
+#[derive(Debug, Clone)]
+pub struct PipelineOptions {
+    pub workers: usize,
+    pub queue_capacity: usize,
+    pub drop_policy: DropPolicy
+}
+
+impl PipelineOptions {
+    pub fn new(workers: usize) -> PipelineOptions {
+        PipelineOptions {
+            workers,
+            ..PipelineOptions::default()
+        }
+    }
+}
+
+impl Default for PipelineOptions {
+    fn default() -> Self {
+        PipelineOptions {
+            workers: thread::available_parallelism().map(|n| n.get().saturating_sub(1)).unwrap_or(1).max(1),
+            queue_capacity: 4096,
+            drop_policy: DropPolicy::Block
+        }
+    }
+}
+
+struct Queue<T> {
+    sender: SyncSender<T>,
+    policy: DropPolicy,
+    stats: Arc<QueueCounters>
+}
+
+impl<T> Queue<T> {
+    fn new(sender: SyncSender<T>, policy: DropPolicy, stats: Arc<QueueCounters>) -> Queue<T> {
+        Queue {
+            sender,
+            policy,
+            stats
+        }
+    }
+
+    fn send(&self, item: T) {
+        let sent = match self.policy {
+            DropPolicy::Block => self.sender.send(item).is_ok(),
+            DropPolicy::DropNewest => self.sender.try_send(item).is_ok()
+        };
+
+        self.stats.sent(sent);
+    }
+}
+
+impl<T> Clone for Queue<T> {
+    fn clone(&self) -> Self {
+        Queue::new(self.sender.clone(), self.policy, self.stats.clone())
+    }
+}
+
+impl Sniffer {
+    // Captures on the calling thread and hands frames to `options.workers` threads,
+    // each running its own handler built by `handlers`. Both directions of a flow
+    // always go to the same worker.
+    pub fn sniff_parallel<F, H, V>(&self, options: &PipelineOptions, handlers: F) -> Result<Statistics, CaptureError>
+    where
+        F: Fn(usize) -> H,
+        H: FnMut(CapturedPacket) -> V + Send,
+        V: Into<Verdict>
+    {
+        let workers = options.workers.max(1);
+        let capacity = options.queue_capacity.max(1);
+        let mut session = Session::open(self)?;
+        let savefile = session.take_savefile();
+        // A handler stopping ends this capture only, not other clones of the sniffer.
+        let cancel = session.cancel_handle();
+        let worker_queues: Vec<_> = (0..workers).map(|worker| self.stats.queue(format!("worker-{}", worker))).collect();
+
+        let result = thread::scope(|scope| {
+            let (writer, written) = match savefile {
+                Some(mut savefile) => {
+                    let (sender, receiver) = mpsc::sync_channel::<(Frame, Option<Verdict>)>(capacity);
+                    let queue = self.stats.queue("savefile");
+                    let (processed, cancel) = (queue.clone(), cancel.clone());
+
+                    // A failed write ends the capture, as it does in `sniff`.
+                    let written = scope.spawn(move || -> io::Result<()> {
+                        let written = (|| {
+                            for (frame, verdict) in receiver {
+                                savefile.write(verdict, frame.interface, frame.timestamp, frame.len, &frame.data)?;
+                                processed.processed();
+                            }
+                            savefile.finish()
+                        })();
+                        if written.is_err() {
+                            cancel.stop();
+                        }
+                        written
+                    });
+                    (Some(Queue::new(sender, options.drop_policy, queue)), Some(written))
+                }
+                None => (None, None)
+            };
+
+            let mut queues = Vec::with_capacity(workers);
+            for (worker, queue) in worker_queues.into_iter().enumerate() {
+                let (sender, receiver) = mpsc::sync_channel::<Frame>(capacity);
+                let mut handler = handlers(worker);
+                let writer = writer.clone();
+                let (counters, processed, cancel) = (self.stats.worker(), queue.clone(), cancel.clone());
+
+                scope.spawn(move || {
+                    for frame in receiver {
+                        let verdict = frame.decode(&counters).map(|packet| handler(packet).into());
+                        counters.verdict(verdict);
+                        processed.processed();
+
+                        if let Some(Verdict::Stop) = verdict {
+                            cancel.stop();
+                        }
+                        if let Some(writer) = &writer {
+                            writer.send((frame, verdict));
+                        }
+                    }
+                });
+                queues.push(Queue::new(sender, options.drop_policy, queue));
+            }
+            drop(writer);
+
+            let captured = (|| {
+                while let Some(frame) = session.next_frame()? {
+                    let worker = (flow_hash(frame.link_type, &frame.data) % workers as u64) as usize;
+                    queues[worker].send(frame);
+                }
+                Ok(())
+            })();
+            drop(queues);
+
+            let written = match written.map(|written| written.join()) {
+                Some(Ok(written)) => written.map_err(CaptureError::from),
+                Some(Err(e)) => panic::resume_unwind(e),
+                None => Ok(())
+            };
+            captured.and(written)
+        });
+        result?;
+        session.finish()
+    }
+}
+
+// Orders the endpoints before hashing so that both directions of a flow get the
+// same value. Frames without an IP header all hash to 0.
+fn flow_hash(link_type: u16, data: &[u8]) -> u64 {
+    match flow_key(link_type, data) {
+        Some((mut a, mut b, protocol)) => {
+            if b < a {
+                mem::swap(&mut a, &mut b);
+            }
+            let mut hasher = DefaultHasher::new();
+            (a, b, protocol).hash(&mut hasher);
+            hasher.finish()
+        }
+        None => 0
+    }
+}
+
+type Endpoint<'a> = (&'a [u8], u16);
+
+fn flow_key(link_type: u16, data: &[u8]) -> Option<(Endpoint<'_>, Endpoint<'_>, u8)> {
+    let (ether_type, offset) = network_layer(link_type, data)?;
+    let ip = data.get(offset..)?;
+    let (src, dst, protocol, transport) = match ether_type {
+        ETHERTYPE_IPV4 => {
+            let ihl = (*ip.first()? & 0x0F) as usize * 4;
+            // Only unfragmented datagrams carry ports in every packet.
+            let fragmented = u16::from_be_bytes([*ip.get(6)?, *ip.get(7)?]) & 0x3FFF != 0;
+            (ip.get(12..16)?, ip.get(16..20)?, *ip.get(9)?, if fragmented { None } else { ip.get(ihl..) })
+        }
+        ETHERTYPE_IPV6 => (ip.get(8..24)?, ip.get(24..40)?, *ip.get(6)?, ip.get(40..)),
+        _ => return None
+    };
+
+    let (src_port, dst_port) = match (protocol, transport) {
+        (6 | 17, Some(ports)) if ports.len() >= 4 => {
+            (u16::from_be_bytes([ports[0], ports[1]]), u16::from_be_bytes([ports[2], ports[3]]))
+        }
+        _ => (0, 0)
+    };
+    Some(((src, src_port), (dst, dst_port), protocol))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::time::Duration;
+
+    use crate::common::fixtures::ethernet_udp;
+    use crate::common::network::link::internet::transport::TransportHeader;
+    use crate::common::pcap::captured::CapturedPacket;
+    use crate::common::pcap::pipeline::PipelineOptions;
+    use crate::common::pcap::savefile::Rotation;
+    use crate::common::pcap::source::{ChannelSource, Frame, MemorySource};
+    use crate::common::pcap::{CaptureError, Sniffer};
+
+    #[test]
+    fn keeps_both_directions_of_a_flow_on_one_worker() {
+        let mut frames = vec![];
+        for port in 1000..1016 {
+            for _ in 0..4 {
+                frames.push(ethernet_udp([10, 0, 0, 1], [10, 0, 0, 2], port, 53, &[]));
+                frames.push(ethernet_udp([10, 0, 0, 2], [10, 0, 0, 1], 53, port, &[]));
+            }
+        }
+        let sniffer = Sniffer::builder().source(MemorySource::new(1, frames)).build().unwrap();
+
+        let workers: Arc<Mutex<BTreeMap<u16, BTreeSet<usize>>>> = Arc::default();
+        let stats = sniffer.sniff_parallel(&PipelineOptions::new(4), |worker| {
+            let workers = workers.clone();
+            move |packet: CapturedPacket| {
+                if let Some(TransportHeader::UDP(header)) = &packet.packet.tp_header {
+                    let client = header.src_port.max(header.dst_port);
+                    workers.lock().unwrap().entry(client).or_default().insert(worker);
+                }
+                false
+            }
+        }).unwrap();
+
+        let workers = workers.lock().unwrap();
+        assert_eq!(workers.len(), 16);
+        assert!(workers.values().all(|workers| workers.len() == 1));
+        assert_eq!((stats.captured, stats.decoded), (128, 128));
+    }
+
+    #[test]
+    fn failed_savefile_write_ends_capture() {
+        let (sender, receiver) = mpsc::channel();
+        let path = std::env::temp_dir().join("network-sniffer-missing").join("capture.pcap");
+        // Rotation creates the first file with the first frame, so opening succeeds.
+        let sniffer = Sniffer::builder()
+            .source(ChannelSource::new(1, receiver).timeout(Duration::from_millis(10)))
+            .savefile(path)
+            .savefile_rotation(Rotation::new())
+            .build()
+            .unwrap();
+        sender.send(Frame::new(1, Duration::ZERO, ethernet_udp([10, 0, 0, 1], [10, 0, 0, 2], 1, 2, &[]))).unwrap();
+
+        // The sender stays alive, so only the failed write can end the capture.
+        let result = sniffer.sniff_parallel(&PipelineOptions::new(2), |_| |_| false);
+        assert!(matches!(result, Err(CaptureError::Savefile(_))));
+        drop(sender);
+    }
+}
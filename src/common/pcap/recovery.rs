@@ -0,0 +1,109 @@
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::common::pcap::CaptureError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterfaceFault {
+    Down,
+    Removed,
+    PermissionDenied
+}
+
+impl Display for InterfaceFault {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InterfaceFault::Down => write!(f, "interface went down"),
+            InterfaceFault::Removed => write!(f, "interface was removed"),
+            InterfaceFault::PermissionDenied => write!(f, "permission denied")
+        }
+    }
+}
+
+// Reopening only helps with interfaces that go away and come back; missing
+// permissions always end the capture.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum RecoveryPolicy {
+    #[default]
+    Fail,
+    Reopen {
+        initial_backoff: Duration,
+        max_backoff: Duration,
+        max_attempts: Option<u32>
+    }
+}
+
+impl RecoveryPolicy {
+    pub fn reopen() -> RecoveryPolicy {
+        RecoveryPolicy::Reopen {
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            max_attempts: None
+        }
+    }
+
+    // Doubles with every attempt, starting at 1. None once attempts run out.
+    pub fn backoff(&self, attempt: u32) -> Option<Duration> {
+        match self {
+            RecoveryPolicy::Fail => None,
+            RecoveryPolicy::Reopen { initial_backoff, max_backoff, max_attempts } => {
+                if max_attempts.is_some_and(|max_attempts| attempt > max_attempts) {
+                    return None;
+                }
+                let factor = 1u32.checked_shl(attempt.saturating_sub(1)).unwrap_or(u32::MAX);
+                Some(initial_backoff.saturating_mul(factor).min(*max_backoff))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum LifecycleEvent {
+    Opened { interface: String },
+    Lost { interface: String, fault: InterfaceFault, message: String },
+    Reopening { interface: String, attempt: u32, delay: Duration },
+    Reopened { interface: String, attempts: u32 },
+    GaveUp { interface: String, attempts: u32 }
+}
+
+pub type LifecycleHook = Arc<dyn Fn(&LifecycleEvent) + Send + Sync>;
+
+// Turns errors that mean the interface itself is gone into `InterfaceFault`s.
+// libpcap only reports most of them as text, so its messages are matched.
+pub(in crate::common::pcap) fn classify(error: CaptureError) -> CaptureError {
+    let fault = match &error {
+        CaptureError::Pcap(pcap::Error::PcapError(message)) => fault_from_message(message),
+        CaptureError::Pcap(pcap::Error::ErrnoError(errno)) => fault_from_io(&io::Error::from_raw_os_error(errno.0)),
+        CaptureError::Socket(e) => fault_from_io(e),
+        _ => None
+    };
+
+    match fault {
+        Some(fault) => CaptureError::InterfaceFault(fault, error.to_string()),
+        None => error
+    }
+}
+
+fn fault_from_io(error: &io::Error) -> Option<InterfaceFault> {
+    match error.kind() {
+        io::ErrorKind::NetworkDown => Some(InterfaceFault::Down),
+        io::ErrorKind::PermissionDenied => Some(InterfaceFault::PermissionDenied),
+        _ => fault_from_message(&error.to_string())
+    }
+}
+
+fn fault_from_message(message: &str) -> Option<InterfaceFault> {
+    let message = message.to_lowercase();
+
+    if message.contains("went down") || message.contains("network is down") {
+        Some(InterfaceFault::Down)
+    } else if message.contains("no such device") || message.contains("disappeared") || message.contains("device not configured") {
+        Some(InterfaceFault::Removed)
+    } else if message.contains("permission") || message.contains("not permitted") {
+        Some(InterfaceFault::PermissionDenied)
+    } else {
+        None
+    }
+}
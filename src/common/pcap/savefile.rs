@@ -0,0 +1,524 @@
+use std::collections::VecDeque;
+use std::fmt::{Debug, Formatter};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Error, ErrorKind, Result, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::common::network::datalink::LINKTYPE_ETHERNET;
+use crate::common::pcap::source::SourceInterface;
+use crate::common::pcap::Verdict;
+use crate::common::pcapng::writer::PcapNgWriter;
+
+const PCAP_NANOSECOND_MAGIC: u32 = 0xA1B23C4D;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SavefileFormat {
+    Pcap,
+    PcapNg
+}
+
+impl SavefileFormat {
+    pub fn from_path(path: &Path) -> SavefileFormat {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("pcapng") => SavefileFormat::PcapNg,
+            _ => SavefileFormat::Pcap
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SavefileMode {
+    All,
+    Accepted
+}
+
+pub type RotateHook = Arc<dyn Fn(&Path) + Send + Sync>;
+
+#[derive(Clone, Default)]
+pub struct Rotation {
+    pub max_bytes: Option<u64>,
+    pub max_duration: Option<Duration>,
+    pub max_files: Option<usize>,
+    pub on_rotate: Option<RotateHook>
+}
+
+impl Rotation {
+    pub fn new() -> Rotation {
+        Rotation::default()
+    }
+
+    pub fn max_megabytes(mut self, megabytes: u64) -> Rotation {
+        self.max_bytes = Some(megabytes * 1024 * 1024);
+        self
+    }
+
+    pub fn max_duration(mut self, duration: Duration) -> Rotation {
+        self.max_duration = Some(duration);
+        self
+    }
+
+    pub fn max_files(mut self, files: usize) -> Rotation {
+        self.max_files = Some(files);
+        self
+    }
+
+    pub fn on_rotate(mut self, f: impl Fn(&Path) + Send + Sync + 'static) -> Rotation {
+        self.on_rotate = Some(Arc::new(f));
+        self
+    }
+
+    fn is_due(&self, savefile: &Savefile, started: Duration, timestamp: Duration) -> bool {
+        self.max_bytes.is_some_and(|max_bytes| savefile.bytes_written() >= max_bytes)
+            || self.max_duration.is_some_and(|max_duration| timestamp.saturating_sub(started) >= max_duration)
+    }
+}
+
+impl Debug for Rotation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Rotation")
+            .field("max_bytes", &self.max_bytes)
+            .field("max_duration", &self.max_duration)
+            .field("max_files", &self.max_files)
+            .field("on_rotate", &self.on_rotate.is_some())
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SavefileOptions {
+    pub path: PathBuf,
+    pub format: SavefileFormat,
+    pub mode: SavefileMode,
+    pub rotation: Option<Rotation>
+}
+
+impl SavefileOptions {
+    pub fn new(path: impl Into<PathBuf>) -> SavefileOptions {
+        let path = path.into();
+        SavefileOptions {
+            format: SavefileFormat::from_path(&path),
+            mode: SavefileMode::All,
+            rotation: None,
+            path
+        }
+    }
+
+    // Frames that couldn't be decoded never reach the callback, so they have no verdict.
+    pub fn wants(&self, verdict: Option<Verdict>) -> bool {
+        match self.mode {
+            SavefileMode::All => true,
+            SavefileMode::Accepted => matches!(verdict, Some(Verdict::Accept) | Some(Verdict::Stop))
+        }
+    }
+}
+
+// Writes every file of a capture: a single one at the configured path, or a
+// series of timestamped ones when rotation is enabled.
+pub struct SavefileSink {
+    options: SavefileOptions,
+    interfaces: Vec<SourceInterface>,
+    snap_len: u32,
+    current: Option<(Savefile, PathBuf, Duration)>,
+    files: VecDeque<PathBuf>
+}
+
+impl SavefileSink {
+    // Files rotated by earlier captures count towards `max_files` as well.
+    pub fn new(options: SavefileOptions, interfaces: Vec<SourceInterface>, snap_len: u32) -> Result<SavefileSink> {
+        let files = match &options.rotation {
+            Some(rotation) if rotation.max_files.is_some() => rotated_files(&options.path)?.into(),
+            _ => VecDeque::new()
+        };
+        let mut sink = SavefileSink {
+            options,
+            interfaces,
+            snap_len,
+            current: None,
+            files
+        };
+
+        if sink.options.rotation.is_none() {
+            let path = sink.options.path.clone();
+            let savefile = Savefile::create(&path, sink.options.format, &sink.interfaces, sink.snap_len)?;
+            sink.current = Some((savefile, path, Duration::ZERO));
+        }
+        Ok(sink)
+    }
+
+    pub fn options(&self) -> &SavefileOptions {
+        &self.options
+    }
+
+    pub fn write(&mut self, verdict: Option<Verdict>, interface: u32, timestamp: Duration, original_len: u32, data: &[u8]) -> Result<()> {
+        if !self.options.wants(verdict) {
+            return Ok(());
+        }
+
+        if let Some(rotation) = &self.options.rotation {
+            let due = match &self.current {
+                Some((savefile, _, started)) => rotation.is_due(savefile, *started, timestamp),
+                None => true
+            };
+            if due {
+                self.close()?;
+                let (savefile, path) = self.create_rotated(timestamp)?;
+                self.current = Some((savefile, path, timestamp));
+            }
+        }
+
+        match &mut self.current {
+            Some((savefile, _, _)) => savefile.write(interface, timestamp, original_len, data),
+            None => Ok(())
+        }
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        match &mut self.current {
+            Some((savefile, _, _)) => savefile.flush(),
+            None => Ok(())
+        }
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        self.close()
+    }
+
+    // Frames can share a timestamp, so a counter is added to the name until it
+    // is new rather than overwriting a file that was just rotated.
+    fn create_rotated(&mut self, timestamp: Duration) -> Result<(Savefile, PathBuf)> {
+        let mut counter = 0;
+        let (file, path) = loop {
+            let path = rotated_path(&self.options.path, timestamp, counter);
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => break (file, path),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => counter += 1,
+                Err(e) => return Err(e)
+            }
+        };
+        let savefile = Savefile::new(file, self.options.format, &self.interfaces, self.snap_len)?;
+        self.files.push_back(path.clone());
+
+        let max_files = self.options.rotation.as_ref().and_then(|rotation| rotation.max_files);
+        while max_files.is_some_and(|max_files| self.files.len() > max_files.max(1)) {
+            // The rotate hook may already have moved or compressed the file.
+            if let Some(oldest) = self.files.pop_front() {
+                match std::fs::remove_file(oldest) {
+                    Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+                    _ => {}
+                }
+            }
+        }
+        Ok((savefile, path))
+    }
+
+    fn close(&mut self) -> Result<()> {
+        if let Some((mut savefile, path, _)) = self.current.take() {
+            savefile.flush()?;
+            drop(savefile);
+
+            if let Some(on_rotate) = self.options.rotation.as_ref().and_then(|rotation| rotation.on_rotate.as_ref()) {
+                on_rotate(&path);
+            }
+        }
+        Ok(())
+    }
+}
+
+pub enum Savefile {
+    Pcap(PcapWriter<CountingWriter<BufWriter<File>>>),
+    PcapNg(PcapNgWriter<CountingWriter<BufWriter<File>>>)
+}
+
+impl Savefile {
+    // pcapng gets one interface description per interface, while pcap files
+    // have a single link type for all of them.
+    pub fn create(path: &Path, format: SavefileFormat, interfaces: &[SourceInterface], snap_len: u32) -> Result<Savefile> {
+        Savefile::new(File::create(path)?, format, interfaces, snap_len)
+    }
+
+    fn new(file: File, format: SavefileFormat, interfaces: &[SourceInterface], snap_len: u32) -> Result<Savefile> {
+        let link_type = interfaces.first().map_or(LINKTYPE_ETHERNET, |interface| interface.link_type);
+        if format == SavefileFormat::Pcap && interfaces.iter().any(|interface| interface.link_type != link_type) {
+            return Err(Error::new(ErrorKind::InvalidInput, "interfaces with different link types need a pcapng savefile"));
+        }
+        let file = CountingWriter::new(BufWriter::new(file));
+
+        Ok(match format {
+            SavefileFormat::Pcap => Savefile::Pcap(PcapWriter::new(file, link_type, snap_len)?),
+            SavefileFormat::PcapNg => {
+                let mut writer = PcapNgWriter::new(file)?;
+                for interface in interfaces {
+                    writer.add_interface(interface.link_type, snap_len, interface.name.as_deref())?;
+                }
+                Savefile::PcapNg(writer)
+            }
+        })
+    }
+
+    pub fn write(&mut self, interface: u32, timestamp: Duration, original_len: u32, data: &[u8]) -> Result<()> {
+        match self {
+            Savefile::Pcap(writer) => writer.write_packet(timestamp, original_len, data),
+            Savefile::PcapNg(writer) => writer.write_packet(interface, timestamp, original_len, data, None)
+        }
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        match self {
+            Savefile::Pcap(writer) => writer.flush(),
+            Savefile::PcapNg(writer) => writer.flush()
+        }
+    }
+
+    pub fn bytes_written(&self) -> u64 {
+        match self {
+            Savefile::Pcap(writer) => writer.get_ref().written,
+            Savefile::PcapNg(writer) => writer.get_ref().written
+        }
+    }
+}
+
+pub struct CountingWriter<W: Write> {
+    writer: W,
+    written: u64
+}
+
+impl<W: Write> CountingWriter<W> {
+    fn new(writer: W) -> CountingWriter<W> {
+        CountingWriter {
+            writer,
+            written: 0
+        }
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let n = self.writer.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()
+    }
+}
+
+pub struct PcapWriter<W: Write> {
+    writer: W
+}
+
+impl<W: Write> PcapWriter<W> {
+    pub fn new(mut writer: W, link_type: u16, snap_len: u32) -> Result<PcapWriter<W>> {
+        writer.write_all(&PCAP_NANOSECOND_MAGIC.to_le_bytes())?;
+        writer.write_all(&2u16.to_le_bytes())?;
+        writer.write_all(&4u16.to_le_bytes())?;
+        writer.write_all(&0i32.to_le_bytes())?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        writer.write_all(&snap_len.to_le_bytes())?;
+        writer.write_all(&u32::from(link_type).to_le_bytes())?;
+
+        Ok(PcapWriter {
+            writer
+        })
+    }
+
+    pub fn write_packet(&mut self, timestamp: Duration, original_len: u32, data: &[u8]) -> Result<()> {
+        self.writer.write_all(&(timestamp.as_secs() as u32).to_le_bytes())?;
+        self.writer.write_all(&timestamp.subsec_nanos().to_le_bytes())?;
+        self.writer.write_all(&(data.len() as u32).to_le_bytes())?;
+        self.writer.write_all(&original_len.to_le_bytes())?;
+        self.writer.write_all(data)
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+// `capture.pcap` becomes `capture-20240131-235959-123456.pcap`, named after the
+// capture time of the first frame in the file, then `capture-20240131-235959-123456-1.pcap`
+// and so on for later files starting at the same time.
+fn rotated_path(path: &Path, timestamp: Duration, counter: u32) -> PathBuf {
+    let secs = timestamp.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let mut stamp = format!("{:04}{:02}{:02}-{:02}{:02}{:02}-{:06}",
+                            year, month, day, secs / 3600 % 24, secs / 60 % 60, secs % 60, timestamp.subsec_micros());
+    if counter > 0 {
+        stamp = format!("{}-{}", stamp, counter);
+    }
+
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    let name = match path.extension() {
+        Some(extension) => format!("{}-{}.{}", stem, stamp, extension.to_string_lossy()),
+        None => format!("{}-{}", stem, stamp)
+    };
+    path.with_file_name(name)
+}
+
+// Files named by `rotated_path` for the same base path, oldest first.
+fn rotated_files(path: &Path) -> Result<Vec<PathBuf>> {
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new(".")
+    };
+    let entries = match std::fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e)
+    };
+
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    let suffix = path.extension().map(|extension| format!(".{}", extension.to_string_lossy())).unwrap_or_default();
+    let mut files = vec![];
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let stamp = name.strip_prefix(&stem).and_then(|name| name.strip_prefix('-')).and_then(|name| name.strip_suffix(&suffix));
+        if let Some(order) = stamp.and_then(rotation_order) {
+            if entry.file_type()?.is_file() {
+                files.push((order, entry.path()));
+            }
+        }
+    }
+    files.sort();
+    Ok(files.into_iter().map(|(_, path)| path).collect())
+}
+
+// The time stamp sorts chronologically as text, the counter only as a number.
+fn rotation_order(stamp: &str) -> Option<(String, u32)> {
+    let parts: Vec<&str> = stamp.split('-').collect();
+    if !parts.iter().all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit())) {
+        return None;
+    }
+    match parts[..] {
+        [date, time, micros] if date.len() == 8 && time.len() == 6 && micros.len() == 6 => Some((stamp.to_string(), 0)),
+        [date, time, micros, counter] if date.len() == 8 && time.len() == 6 && micros.len() == 6 => {
+            Some((format!("{}-{}-{}", date, time, micros), counter.parse().ok()?))
+        }
+        _ => None
+    }
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use crate::common::pcap::savefile::{Rotation, SavefileOptions, SavefileSink};
+    use crate::common::pcap::source::SourceInterface;
+
+    const HEADER_SIZE: u64 = 24;
+    const RECORD_SIZE: u64 = 16;
+
+    fn directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("network-sniffer-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    fn sink(path: PathBuf, rotation: Rotation) -> SavefileSink {
+        let mut options = SavefileOptions::new(path);
+        options.rotation = Some(rotation);
+        SavefileSink::new(options, vec![SourceInterface { name: None, link_type: 1 }], 65535).unwrap()
+    }
+
+    // Sorted by name, which isn't the rotation order once counters reach 10.
+    fn files(directory: &PathBuf) -> Vec<(String, u64)> {
+        let mut files: Vec<_> = std::fs::read_dir(directory).unwrap()
+            .map(|entry| entry.unwrap())
+            .map(|entry| (entry.file_name().into_string().unwrap(), entry.metadata().unwrap().len()))
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn rotates_twice_on_same_timestamp() {
+        let directory = directory("same-timestamp");
+        // Every write is due for rotation.
+        let mut sink = sink(directory.join("capture.pcap"), Rotation::new().max_megabytes(0));
+        for len in [4, 5, 6] {
+            sink.write(None, 0, Duration::ZERO, len, &vec![0; len as usize]).unwrap();
+        }
+        sink.finish().unwrap();
+
+        assert_eq!(files(&directory), [
+            ("capture-19700101-000000-000000-1.pcap".to_string(), HEADER_SIZE + RECORD_SIZE + 5),
+            ("capture-19700101-000000-000000-2.pcap".to_string(), HEADER_SIZE + RECORD_SIZE + 6),
+            ("capture-19700101-000000-000000.pcap".to_string(), HEADER_SIZE + RECORD_SIZE + 4)
+        ]);
+    }
+
+    #[test]
+    fn prunes_oldest_file_on_same_timestamp() {
+        let directory = directory("same-timestamp-pruned");
+        let mut sink = sink(directory.join("capture.pcap"), Rotation::new().max_megabytes(0).max_files(2));
+        for len in [4, 5, 6] {
+            sink.write(None, 0, Duration::ZERO, len, &vec![0; len as usize]).unwrap();
+        }
+        sink.finish().unwrap();
+
+        assert_eq!(files(&directory), [
+            ("capture-19700101-000000-000000-1.pcap".to_string(), HEADER_SIZE + RECORD_SIZE + 5),
+            ("capture-19700101-000000-000000-2.pcap".to_string(), HEADER_SIZE + RECORD_SIZE + 6)
+        ]);
+    }
+
+    #[test]
+    fn counts_files_of_earlier_captures() {
+        let directory = directory("earlier-captures");
+        for name in ["capture-20240101-000000-000000.pcap", "capture-20240101-000000-000000-1.pcap", "capture-notes.pcap", "other-20240101-000000-000000.pcap"] {
+            std::fs::write(directory.join(name), b"").unwrap();
+        }
+
+        let mut sink = sink(directory.join("capture.pcap"), Rotation::new().max_files(2));
+        sink.write(None, 0, Duration::from_secs(1_704_067_200), 4, &[0; 4]).unwrap();
+        sink.finish().unwrap();
+
+        let names: Vec<_> = files(&directory).into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, [
+            "capture-20240101-000000-000000-1.pcap",
+            "capture-20240101-000000-000000-2.pcap",
+            "capture-notes.pcap",
+            "other-20240101-000000-000000.pcap"
+        ]);
+    }
+
+    #[test]
+    fn tolerates_files_moved_by_rotate_hook() {
+        let directory = directory("moved-by-hook");
+        let rotation = Rotation::new().max_duration(Duration::from_secs(1)).max_files(1)
+            .on_rotate(|path| std::fs::remove_file(path).unwrap());
+        let mut sink = sink(directory.join("capture.pcap"), rotation);
+        for secs in 0..3 {
+            sink.write(None, 0, Duration::from_secs(secs), 4, &[0; 4]).unwrap();
+        }
+        sink.finish().unwrap();
+
+        assert!(files(&directory).is_empty());
+    }
+}
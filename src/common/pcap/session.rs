@@ -0,0 +1,220 @@
+use std::time::{Duration, Instant};
+
+use crate::common::pcap::captured::CapturedPacket;
+use pcap::Device;
+
+use crate::common::pcap::limits::Limit;
+use crate::common::pcap::options::{Backend, CaptureOptions};
+use crate::common::pcap::savefile::SavefileSink;
+use crate::common::pcap::source::merged::MergedSource;
+use crate::common::pcap::source::recovering::{RecoveringSource, SourceFactory};
+use crate::common::pcap::source::replay::ReplaySource;
+use crate::common::pcap::source::{CaptureSource, Frame, PcapSource, Received};
+use crate::common::pcap::stats::{Statistics, STATS_INTERVAL};
+use crate::common::pcap::stop::StopHandle;
+use crate::common::pcap::{CaptureError, Sniffer, Source, Verdict};
+#[cfg(target_os = "linux")]
+use crate::common::pcap::source::af_packet::AfPacketSource;
+
+fn open_backend(device: &Device, options: &CaptureOptions) -> Result<Box<dyn CaptureSource>, CaptureError> {
+    Ok(match &options.backend {
+        Backend::Pcap => Box::new(PcapSource::live(device.clone(), options)?),
+        #[cfg(target_os = "linux")]
+        Backend::AfPacket(af_packet) => Box::new(AfPacketSource::open(&device.name, options, af_packet)?)
+    })
+}
+
+fn open_device(device: &Device, sniffer: &Sniffer) -> Result<Box<dyn CaptureSource>, CaptureError> {
+    let (device, options) = (device.clone(), sniffer.options.clone());
+    let name = device.name.clone();
+    let open: SourceFactory = Box::new(move || open_backend(&device, &options));
+
+    let source = RecoveringSource::open(name, open, sniffer.recovery.clone(), sniffer.on_lifecycle.clone())?;
+    Ok(Box::new(source.timeout(timeout(&sniffer.options))))
+}
+
+fn timeout(options: &CaptureOptions) -> Duration {
+    Duration::from_millis(options.timeout_ms.max(1) as u64)
+}
+
+// One open capture of a `Sniffer`, shared by the callback and iterator APIs.
+pub(in crate::common::pcap) struct Session<'a> {
+    sniffer: &'a Sniffer,
+    source: Box<dyn CaptureSource>,
+    savefile: Option<SavefileSink>,
+    // Ends only this session, unlike the sniffer's stop handle shared by all clones.
+    cancel: StopHandle,
+    stats_refreshed: Instant,
+    opened: Instant,
+    first_timestamp: Option<Duration>,
+    packets: u64,
+    bytes: u64
+}
+
+impl<'a> Session<'a> {
+    pub fn open(sniffer: &'a Sniffer) -> Result<Session<'a>, CaptureError> {
+        let mut source: Box<dyn CaptureSource> = match &sniffer.source {
+            Source::Device(device) => open_device(device, sniffer)?,
+            Source::Devices(devices) => {
+                let sources = devices.iter().map(|device| open_device(device, sniffer)).collect::<Result<_, _>>()?;
+                Box::new(MergedSource::new(sources).timeout(timeout(&sniffer.options)))
+            }
+            Source::File(path) => Box::new(PcapSource::file(path, sniffer.options.precision)?),
+            Source::Custom(custom) => custom.take()?
+        };
+        // Live devices already deliver frames in real time.
+        if let (Some(speed), Source::File(_) | Source::Custom(_)) = (sniffer.options.replay, &sniffer.source) {
+            source = Box::new(ReplaySource::new(source, speed).timeout(timeout(&sniffer.options)));
+        }
+        sniffer.filter.apply(source.as_mut())?;
+        sniffer.stats.reset();
+        sniffer.stop.reset();
+
+        let savefile = match &sniffer.savefile {
+            Some(options) => Some(SavefileSink::new(options.clone(), source.interfaces(), sniffer.options.snaplen)?),
+            None => None
+        };
+
+        Ok(Session {
+            sniffer,
+            source,
+            savefile,
+            cancel: StopHandle::default(),
+            stats_refreshed: Instant::now(),
+            opened: Instant::now(),
+            first_timestamp: None,
+            packets: 0,
+            bytes: 0
+        })
+    }
+
+    pub fn cancel_on(mut self, cancel: StopHandle) -> Session<'a> {
+        self.cancel = cancel;
+        self
+    }
+
+    pub fn cancel_handle(&self) -> StopHandle {
+        self.cancel.clone()
+    }
+
+    pub fn next_frame(&mut self) -> Result<Option<Frame>, CaptureError> {
+        loop {
+            if self.sniffer.stop.is_stopped() || self.cancel.is_stopped() {
+                return Ok(None);
+            }
+            if let Some(limit) = self.sniffer.limits.reached(self.packets, self.bytes, self.opened.elapsed()) {
+                self.sniffer.stats.update(|stats| stats.limit_reached = Some(limit));
+                return Ok(None);
+            }
+            self.sniffer.filter.apply_if_changed(self.source.as_mut())?;
+            if self.stats_refreshed.elapsed() >= STATS_INTERVAL {
+                self.refresh_stats();
+            }
+
+            match self.source.next_frame()? {
+                Received::Frame(frame) => {
+                    let first = *self.first_timestamp.get_or_insert(frame.timestamp);
+                    if self.sniffer.limits.past_capture_time(first, frame.timestamp) {
+                        self.sniffer.stats.update(|stats| stats.limit_reached = Some(Limit::CaptureTime));
+                        return Ok(None);
+                    }
+
+                    self.packets += 1;
+                    self.bytes += frame.data.len() as u64;
+                    self.sniffer.clock.advance(frame.timestamp);
+                    self.sniffer.stats.counters().captured(frame.data.len());
+                    return Ok(Some(frame));
+                }
+                Received::Timeout => {}
+                Received::Closed => {
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    fn refresh_stats(&mut self) {
+        if let Some(stat) = self.source.stats() {
+            self.sniffer.stats.set_pcap(stat);
+        }
+        self.stats_refreshed = Instant::now();
+    }
+
+    pub fn record(&mut self, frame: &Frame, verdict: Option<Verdict>) -> Result<(), CaptureError> {
+        self.sniffer.stats.counters().verdict(verdict);
+        if let Some(savefile) = &mut self.savefile {
+            savefile.write(verdict, frame.interface, frame.timestamp, frame.len, &frame.data)?;
+        }
+        Ok(())
+    }
+
+    pub fn decode(&self, frame: &Frame) -> Option<CapturedPacket> {
+        frame.decode(self.sniffer.stats.counters())
+    }
+
+    pub fn take_savefile(&mut self) -> Option<SavefileSink> {
+        self.savefile.take()
+    }
+
+    // Frames handed out by the iterator have no callback to judge them, so
+    // every decoded frame counts as accepted.
+    fn next_captured(&mut self) -> Result<Option<CapturedPacket>, CaptureError> {
+        while let Some(frame) = self.next_frame()? {
+            match self.decode(&frame) {
+                Some(packet) => {
+                    self.record(&frame, Some(Verdict::Accept))?;
+                    return Ok(Some(packet));
+                }
+                None => {
+                    self.record(&frame, None)?;
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn finish(mut self) -> Result<Statistics, CaptureError> {
+        self.refresh_stats();
+        if let Some(savefile) = self.savefile.take() {
+            savefile.finish()?;
+        }
+        Ok(self.sniffer.stats.snapshot())
+    }
+}
+
+impl Drop for Session<'_> {
+    fn drop(&mut self) {
+        if let Some(savefile) = self.savefile.take() {
+            let _ = savefile.finish();
+        }
+    }
+}
+
+pub struct Packets<'a> {
+    session: Option<Session<'a>>
+}
+
+impl<'a> Packets<'a> {
+    pub(in crate::common::pcap) fn new(session: Session<'a>) -> Packets<'a> {
+        Packets {
+            session: Some(session)
+        }
+    }
+}
+
+impl Iterator for Packets<'_> {
+    type Item = Result<CapturedPacket, CaptureError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let session = self.session.as_mut()?;
+
+        match session.next_captured() {
+            Ok(Some(packet)) => Some(Ok(packet)),
+            Ok(None) => self.session.take()?.finish().err().map(Err),
+            Err(e) => {
+                self.session = None;
+                Some(Err(e))
+            }
+        }
+    }
+}
@@ -0,0 +1,312 @@
+use std::collections::VecDeque;
+use std::fmt::{Debug, Formatter};
+use std::path::Path;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use pcap::{Activated, BpfProgram, Capture, Device, Linktype, Precision, Stat};
+
+use crate::common::network::packet::Packet;
+use crate::common::pcap::captured::CapturedPacket;
+use crate::common::pcap::options::CaptureOptions;
+use crate::common::pcap::stats::Counters;
+use crate::common::pcap::CaptureError;
+
+#[cfg(target_os = "linux")]
+pub mod af_packet;
+pub mod merged;
+pub mod recovering;
+pub mod replay;
+
+pub struct Frame {
+    pub timestamp: Duration,
+    pub caplen: u32,
+    pub len: u32,
+    // Index into the source's `interfaces()`.
+    pub interface: u32,
+    pub link_type: u16,
+    pub data: Vec<u8>
+}
+
+impl Frame {
+    pub fn new(link_type: u16, timestamp: Duration, data: Vec<u8>) -> Frame {
+        Frame {
+            timestamp,
+            caplen: data.len() as u32,
+            len: data.len() as u32,
+            interface: 0,
+            link_type,
+            data
+        }
+    }
+
+    pub(in crate::common::pcap) fn decode(&self, stats: &Counters) -> Option<CapturedPacket> {
+        let packet = match Packet::from_bytes(self.link_type, &self.data) {
+            Ok(packet) => packet,
+            Err(e) => {
+                stats.decode_error(&e);
+                return None;
+            }
+        };
+        // Layers above the link layer that failed still count against the frame.
+        for e in &packet.errors {
+            stats.decode_error(e);
+        }
+        stats.decoded(packet.lp_header.vlan_tags().first().map(|tag| tag.vid));
+
+        Some(CapturedPacket {
+            timestamp: self.timestamp,
+            caplen: self.caplen,
+            len: self.len,
+            interface: self.interface,
+            link_type: self.link_type,
+            packet
+        })
+    }
+}
+
+pub enum Received {
+    Frame(Frame),
+    // Nothing arrived in time; the caller gets a chance to check for stop
+    // requests and filter changes before asking again. Sources that wait on
+    // their own, like replay pacing or reconnect backoff, return this at
+    // least once per read timeout.
+    Timeout,
+    Closed
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceInterface {
+    pub name: Option<String>,
+    pub link_type: u16
+}
+
+pub trait CaptureSource: Send {
+    fn next_frame(&mut self) -> Result<Received, CaptureError>;
+
+    fn link_type(&self) -> u16;
+
+    fn interfaces(&self) -> Vec<SourceInterface> {
+        vec![SourceInterface { name: None, link_type: self.link_type() }]
+    }
+
+    // An empty expression removes the filter.
+    fn set_filter(&mut self, expression: &str) -> Result<(), CaptureError>;
+
+    fn stats(&mut self) -> Option<Stat> {
+        None
+    }
+}
+
+pub struct PcapSource {
+    cap: Capture<dyn Activated>,
+    name: Option<String>,
+    link_type: u16,
+    nanosecond: bool
+}
+
+impl PcapSource {
+    pub fn live(device: Device, options: &CaptureOptions) -> Result<PcapSource, CaptureError> {
+        let name = device.name.clone();
+        Ok(PcapSource::new(options.open(device)?.into(), Some(name), options.is_nanosecond()))
+    }
+
+    pub fn file(path: &Path, precision: Precision) -> Result<PcapSource, CaptureError> {
+        Ok(PcapSource::new(Capture::from_file_with_precision(path, precision)?.into(), None, precision == Precision::Nano))
+    }
+
+    fn new(cap: Capture<dyn Activated>, name: Option<String>, nanosecond: bool) -> PcapSource {
+        PcapSource {
+            link_type: cap.get_datalink().0 as u16,
+            cap,
+            name,
+            nanosecond
+        }
+    }
+}
+
+impl CaptureSource for PcapSource {
+    fn next_frame(&mut self) -> Result<Received, CaptureError> {
+        match self.cap.next_packet() {
+            Ok(packet) => {
+                // With nanosecond precision pcap stores nanoseconds in `tv_usec`.
+                let subsec = packet.header.ts.tv_usec as u32;
+                let nanos = if self.nanosecond { subsec } else { subsec * 1000 };
+
+                Ok(Received::Frame(Frame {
+                    timestamp: Duration::new(packet.header.ts.tv_sec as u64, nanos),
+                    caplen: packet.header.caplen,
+                    len: packet.header.len,
+                    interface: 0,
+                    link_type: self.link_type,
+                    data: packet.data.to_vec()
+                }))
+            }
+            Err(pcap::Error::NoMorePackets) => {
+                Ok(Received::Closed)
+            }
+            Err(pcap::Error::TimeoutExpired) => {
+                Ok(Received::Timeout)
+            }
+            Err(e) => Err(e.into())
+        }
+    }
+
+    fn link_type(&self) -> u16 {
+        self.link_type
+    }
+
+    fn interfaces(&self) -> Vec<SourceInterface> {
+        vec![SourceInterface { name: self.name.clone(), link_type: self.link_type }]
+    }
+
+    fn set_filter(&mut self, expression: &str) -> Result<(), CaptureError> {
+        self.cap.filter(expression, true).map_err(|e| CaptureError::InvalidFilter(expression.to_string(), e))
+    }
+
+    // Savefiles don't keep kernel counters, so stats are only available live.
+    fn stats(&mut self) -> Option<Stat> {
+        self.cap.stats().ok()
+    }
+}
+
+// Runs a compiled filter in user space for sources without a kernel to do it.
+pub(in crate::common::pcap) struct OfflineFilter(BpfProgram);
+
+// The program is owned and only read while filtering.
+unsafe impl Send for OfflineFilter {}
+
+impl OfflineFilter {
+    pub(in crate::common::pcap) fn compile(expression: &str, link_type: u16) -> Result<Option<OfflineFilter>, CaptureError> {
+        if expression.is_empty() {
+            return Ok(None);
+        }
+        let program = Capture::dead(Linktype(i32::from(link_type)))?
+            .compile(expression, true)
+            .map_err(|e| CaptureError::InvalidFilter(expression.to_string(), e))?;
+        Ok(Some(OfflineFilter(program)))
+    }
+}
+
+pub(in crate::common::pcap) fn matches(filter: &Option<OfflineFilter>, frame: &Frame) -> bool {
+    filter.as_ref().is_none_or(|filter| filter.0.filter(&frame.data))
+}
+
+pub struct MemorySource {
+    link_type: u16,
+    frames: VecDeque<Frame>,
+    filter: Option<OfflineFilter>
+}
+
+impl MemorySource {
+    // Every frame gets a zero timestamp; use `from_frames` to set them.
+    pub fn new(link_type: u16, frames: Vec<Vec<u8>>) -> MemorySource {
+        MemorySource::from_frames(link_type, frames.into_iter().map(|data| Frame::new(link_type, Duration::ZERO, data)).collect())
+    }
+
+    pub fn from_frames(link_type: u16, frames: Vec<Frame>) -> MemorySource {
+        MemorySource {
+            link_type,
+            frames: frames.into(),
+            filter: None
+        }
+    }
+}
+
+impl CaptureSource for MemorySource {
+    fn next_frame(&mut self) -> Result<Received, CaptureError> {
+        while let Some(frame) = self.frames.pop_front() {
+            if matches(&self.filter, &frame) {
+                return Ok(Received::Frame(frame));
+            }
+        }
+        Ok(Received::Closed)
+    }
+
+    fn link_type(&self) -> u16 {
+        self.link_type
+    }
+
+    fn set_filter(&mut self, expression: &str) -> Result<(), CaptureError> {
+        self.filter = OfflineFilter::compile(expression, self.link_type)?;
+        Ok(())
+    }
+}
+
+// Reads frames sent from another thread until every sender is dropped.
+pub struct ChannelSource {
+    link_type: u16,
+    receiver: Receiver<Frame>,
+    timeout: Duration,
+    filter: Option<OfflineFilter>
+}
+
+impl ChannelSource {
+    pub fn new(link_type: u16, receiver: Receiver<Frame>) -> ChannelSource {
+        ChannelSource {
+            link_type,
+            receiver,
+            timeout: Duration::from_millis(100),
+            filter: None
+        }
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> ChannelSource {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl CaptureSource for ChannelSource {
+    fn next_frame(&mut self) -> Result<Received, CaptureError> {
+        match self.receiver.recv_timeout(self.timeout) {
+            Ok(frame) if matches(&self.filter, &frame) => Ok(Received::Frame(frame)),
+            Ok(_) | Err(RecvTimeoutError::Timeout) => Ok(Received::Timeout),
+            Err(RecvTimeoutError::Disconnected) => Ok(Received::Closed)
+        }
+    }
+
+    fn link_type(&self) -> u16 {
+        self.link_type
+    }
+
+    fn set_filter(&mut self, expression: &str) -> Result<(), CaptureError> {
+        self.filter = OfflineFilter::compile(expression, self.link_type)?;
+        Ok(())
+    }
+}
+
+// A source handed to the builder. It can only be opened once, since most
+// sources can't be rewound, but the handle is shared by clones of the sniffer.
+#[derive(Clone)]
+pub struct SharedSource {
+    link_type: u16,
+    source: Arc<Mutex<Option<Box<dyn CaptureSource>>>>
+}
+
+impl SharedSource {
+    pub fn new(source: Box<dyn CaptureSource>) -> SharedSource {
+        SharedSource {
+            link_type: source.link_type(),
+            source: Arc::new(Mutex::new(Some(source)))
+        }
+    }
+
+    pub fn link_type(&self) -> u16 {
+        self.link_type
+    }
+
+    pub(in crate::common::pcap) fn take(&self) -> Result<Box<dyn CaptureSource>, CaptureError> {
+        self.source.lock().unwrap().take().ok_or(CaptureError::SourceConsumed)
+    }
+}
+
+impl Debug for SharedSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SharedSource")
+            .field("link_type", &self.link_type)
+            .field("available", &self.source.lock().unwrap().is_some())
+            .finish()
+    }
+}
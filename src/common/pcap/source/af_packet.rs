@@ -0,0 +1,423 @@
+use std::ffi::CString;
+use std::io;
+use std::mem;
+use std::ptr;
+use std::time::Duration;
+
+use pcap::{Capture, Linktype, Stat};
+
+use crate::common::network::datalink::{LINKTYPE_ETHERNET, LINKTYPE_RAW};
+use crate::common::network::ethernet2::ETHERTYPE_VLAN;
+use crate::common::pcap::options::CaptureOptions;
+use crate::common::pcap::source::{matches, CaptureSource, Frame, OfflineFilter, Received, SourceInterface};
+use crate::common::pcap::CaptureError;
+
+const ARPHRD_NONE: u16 = 0xfffe;
+const TPACKET_ALIGNMENT: u32 = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FanoutMode {
+    Hash,
+    LoadBalance,
+    Cpu,
+    Rollover,
+    Random,
+    QueueMapping
+}
+
+impl FanoutMode {
+    fn value(self) -> u32 {
+        match self {
+            FanoutMode::Hash => libc::PACKET_FANOUT_HASH,
+            FanoutMode::LoadBalance => libc::PACKET_FANOUT_LB,
+            FanoutMode::Cpu => libc::PACKET_FANOUT_CPU,
+            FanoutMode::Rollover => libc::PACKET_FANOUT_ROLLOVER,
+            FanoutMode::Random => libc::PACKET_FANOUT_RND,
+            FanoutMode::QueueMapping => libc::PACKET_FANOUT_QM
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fanout {
+    pub group: u16,
+    pub mode: FanoutMode
+}
+
+#[derive(Debug, Clone)]
+pub struct AfPacketOptions {
+    pub block_size: u32,
+    pub block_count: u32,
+    pub frame_size: u32,
+    pub block_timeout_ms: u32,
+    pub fanout: Option<Fanout>
+}
+
+impl AfPacketOptions {
+    // Takes effect with several sockets in the group, see `AfPacketSource::open_group`.
+    pub fn fanout(mut self, group: u16, mode: FanoutMode) -> AfPacketOptions {
+        self.fanout = Some(Fanout { group, mode });
+        self
+    }
+
+    // The kernel wants frames aligned to 16 bytes and at least one per block.
+    fn validate(&self) -> Result<(), CaptureError> {
+        let message = if self.block_size == 0 || self.block_count == 0 {
+            "block size and count must not be zero"
+        } else if self.frame_size == 0 || !self.frame_size.is_multiple_of(TPACKET_ALIGNMENT) {
+            "frame size must be a non-zero multiple of 16"
+        } else if self.frame_size > self.block_size {
+            "frame size must not exceed the block size"
+        } else {
+            return Ok(());
+        };
+        Err(CaptureError::Socket(io::Error::new(io::ErrorKind::InvalidInput, message)))
+    }
+}
+
+impl Default for AfPacketOptions {
+    fn default() -> Self {
+        AfPacketOptions {
+            block_size: 1 << 20,
+            block_count: 16,
+            frame_size: 2048,
+            block_timeout_ms: 60,
+            fanout: None
+        }
+    }
+}
+
+// A TPACKET_V3 ring: the kernel fills whole blocks of packets and hands them
+// over by flipping the block status, so no syscall is made per packet.
+//
+// This is a copying backend. Every frame is copied out of its block, since
+// `Frame` owns its data and may outlive the block, which goes back to the
+// kernel once its last frame has been read. What it saves over libpcap is the
+// syscall per packet, not the copy.
+//
+// A socket joins a fanout group on its own, so one source is one member and a
+// `Sniffer` only ever opens one. Use `open_group` for several members and run
+// each, for example as the source of its own `Sniffer`, on its own thread.
+pub struct AfPacketSource {
+    name: String,
+    fd: libc::c_int,
+    ring: *mut u8,
+    ring_len: usize,
+    block_size: usize,
+    block_count: usize,
+    // Block currently handed to user space and the offset of its next packet.
+    block: usize,
+    packet: Option<(usize, u32)>,
+    snaplen: u32,
+    timeout_ms: i32,
+    link_type: u16,
+    stats: Stat,
+    // VLAN filters run here, see `set_filter`.
+    user_filter: Option<OfflineFilter>
+}
+
+// The ring is only ever touched through `&mut self`.
+unsafe impl Send for AfPacketSource {}
+
+fn last_error() -> CaptureError {
+    CaptureError::Socket(io::Error::last_os_error())
+}
+
+fn check(result: libc::c_int) -> Result<libc::c_int, CaptureError> {
+    if result < 0 {
+        Err(last_error())
+    } else {
+        Ok(result)
+    }
+}
+
+unsafe fn set_option<T>(fd: libc::c_int, level: libc::c_int, name: libc::c_int, value: &T) -> Result<(), CaptureError> {
+    check(libc::setsockopt(fd, level, name, value as *const T as *const libc::c_void, mem::size_of::<T>() as libc::socklen_t))?;
+    Ok(())
+}
+
+impl AfPacketSource {
+    pub fn open(interface: &str, options: &CaptureOptions, af_packet: &AfPacketOptions) -> Result<AfPacketSource, CaptureError> {
+        af_packet.validate()?;
+        let name = CString::new(interface).map_err(|e| CaptureError::Socket(io::Error::new(io::ErrorKind::InvalidInput, e)))?;
+        let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        if index == 0 {
+            return Err(last_error());
+        }
+
+        let protocol = (libc::ETH_P_ALL as u16).to_be();
+        let fd = check(unsafe { libc::socket(libc::AF_PACKET, libc::SOCK_RAW, protocol as libc::c_int) })?;
+        let mut source = AfPacketSource {
+            name: interface.to_string(),
+            fd,
+            ring: ptr::null_mut(),
+            ring_len: 0,
+            block_size: af_packet.block_size as usize,
+            block_count: af_packet.block_count as usize,
+            block: 0,
+            packet: None,
+            snaplen: options.snaplen,
+            timeout_ms: options.timeout_ms,
+            link_type: LINKTYPE_ETHERNET,
+            stats: Stat { received: 0, dropped: 0, if_dropped: 0 },
+            user_filter: None
+        };
+        // From here on `source` owns the socket and closes it on error.
+        source.setup(index, options, af_packet)?;
+        Ok(source)
+    }
+
+    // Opens `members` sockets in the fanout group set in `af_packet`, so the
+    // kernel spreads the interface's frames across them.
+    pub fn open_group(interface: &str, options: &CaptureOptions, af_packet: &AfPacketOptions, members: usize) -> Result<Vec<AfPacketSource>, CaptureError> {
+        if af_packet.fanout.is_none() {
+            return Err(CaptureError::Socket(io::Error::new(io::ErrorKind::InvalidInput, "a group needs a fanout mode")));
+        }
+        (0..members).map(|_| AfPacketSource::open(interface, options, af_packet)).collect()
+    }
+
+    fn setup(&mut self, index: libc::c_uint, options: &CaptureOptions, af_packet: &AfPacketOptions) -> Result<(), CaptureError> {
+        if let Some(buffer_size) = options.buffer_size {
+            self.block_count = (buffer_size as usize / self.block_size).max(1);
+        }
+
+        let version = libc::tpacket_versions::TPACKET_V3 as libc::c_int;
+        let request = libc::tpacket_req3 {
+            tp_block_size: self.block_size as u32,
+            tp_block_nr: self.block_count as u32,
+            tp_frame_size: af_packet.frame_size,
+            tp_frame_nr: (self.block_size * self.block_count) as u32 / af_packet.frame_size,
+            // Immediate mode retires blocks as soon as possible instead of
+            // waiting for them to fill up.
+            tp_retire_blk_tov: if options.immediate_mode { 1 } else { af_packet.block_timeout_ms },
+            tp_sizeof_priv: 0,
+            tp_feature_req_word: 0
+        };
+        unsafe {
+            set_option(self.fd, libc::SOL_PACKET, libc::PACKET_VERSION, &version)?;
+            set_option(self.fd, libc::SOL_PACKET, libc::PACKET_RX_RING, &request)?;
+        }
+
+        self.ring_len = self.block_size * self.block_count;
+        let ring = unsafe {
+            libc::mmap(ptr::null_mut(), self.ring_len, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_SHARED, self.fd, 0)
+        };
+        if ring == libc::MAP_FAILED {
+            self.ring_len = 0;
+            return Err(last_error());
+        }
+        self.ring = ring as *mut u8;
+
+        let mut address: libc::sockaddr_ll = unsafe { mem::zeroed() };
+        address.sll_family = libc::AF_PACKET as u16;
+        address.sll_protocol = (libc::ETH_P_ALL as u16).to_be();
+        address.sll_ifindex = index as libc::c_int;
+        check(unsafe {
+            libc::bind(self.fd, &address as *const libc::sockaddr_ll as *const libc::sockaddr, mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t)
+        })?;
+
+        let mut length = mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t;
+        check(unsafe {
+            libc::getsockname(self.fd, &mut address as *mut libc::sockaddr_ll as *mut libc::sockaddr, &mut length)
+        })?;
+        if address.sll_hatype == ARPHRD_NONE {
+            self.link_type = LINKTYPE_RAW;
+        }
+
+        if options.promiscuous {
+            let mut membership: libc::packet_mreq = unsafe { mem::zeroed() };
+            membership.mr_ifindex = index as libc::c_int;
+            membership.mr_type = libc::PACKET_MR_PROMISC as u16;
+            unsafe { set_option(self.fd, libc::SOL_PACKET, libc::PACKET_ADD_MEMBERSHIP, &membership)?; }
+        }
+
+        if let Some(fanout) = af_packet.fanout {
+            let value = u32::from(fanout.group) | (fanout.mode.value() << 16);
+            unsafe { set_option(self.fd, libc::SOL_PACKET, libc::PACKET_FANOUT, &value)?; }
+        }
+        Ok(())
+    }
+
+    fn block_desc(&self, block: usize) -> *mut libc::tpacket_block_desc {
+        unsafe { self.ring.add(block * self.block_size) as *mut libc::tpacket_block_desc }
+    }
+
+    fn block_status(&self, block: usize) -> u32 {
+        unsafe { ptr::read_volatile(&(*self.block_desc(block)).hdr.bh1.block_status) }
+    }
+
+    fn release_block(&mut self) {
+        unsafe { ptr::write_volatile(&mut (*self.block_desc(self.block)).hdr.bh1.block_status, libc::TP_STATUS_KERNEL) };
+        self.block = (self.block + 1) % self.block_count;
+        self.packet = None;
+    }
+
+    fn wait(&self) -> Result<bool, CaptureError> {
+        let mut poll = libc::pollfd {
+            fd: self.fd,
+            events: libc::POLLIN | libc::POLLERR,
+            revents: 0
+        };
+        match unsafe { libc::poll(&mut poll, 1, self.timeout_ms) } {
+            result if result < 0 => {
+                let error = io::Error::last_os_error();
+                if error.kind() == io::ErrorKind::Interrupted {
+                    Ok(false)
+                } else {
+                    Err(CaptureError::Socket(error))
+                }
+            }
+            _ if poll.revents & libc::POLLERR != 0 => Err(CaptureError::Socket(self.socket_error())),
+            result => Ok(result > 0)
+        }
+    }
+
+    // The kernel strips the outermost VLAN tag and reports it beside the
+    // packet, so it is put back to show frames as they were on the wire.
+    fn restore_vlan_tag(&self, header: &libc::tpacket3_hdr, data: &[u8]) -> (Vec<u8>, u32) {
+        if self.link_type != LINKTYPE_ETHERNET || header.tp_status & libc::TP_STATUS_VLAN_VALID == 0 || data.len() < 12 {
+            return (data.to_vec(), 0);
+        }
+        let tpid = if header.tp_status & libc::TP_STATUS_VLAN_TPID_VALID != 0 { header.hv1.tp_vlan_tpid } else { ETHERTYPE_VLAN };
+
+        let mut tagged = Vec::with_capacity(data.len() + 4);
+        tagged.extend_from_slice(&data[..12]);
+        tagged.extend_from_slice(&tpid.to_be_bytes());
+        tagged.extend_from_slice(&(header.hv1.tp_vlan_tci as u16).to_be_bytes());
+        tagged.extend_from_slice(&data[12..]);
+        (tagged, 4)
+    }
+
+    // Reading SO_ERROR also clears it.
+    fn socket_error(&self) -> io::Error {
+        let mut error: libc::c_int = 0;
+        let mut length = mem::size_of::<libc::c_int>() as libc::socklen_t;
+        let result = unsafe {
+            libc::getsockopt(self.fd, libc::SOL_SOCKET, libc::SO_ERROR, &mut error as *mut libc::c_int as *mut libc::c_void, &mut length)
+        };
+        if result < 0 {
+            io::Error::last_os_error()
+        } else {
+            io::Error::from_raw_os_error(error)
+        }
+    }
+}
+
+impl AfPacketSource {
+    fn read_frame(&mut self) -> Result<Received, CaptureError> {
+        if self.packet.is_none() {
+            if self.block_status(self.block) & libc::TP_STATUS_USER == 0 && !self.wait()? {
+                return Ok(Received::Timeout);
+            }
+            if self.block_status(self.block) & libc::TP_STATUS_USER == 0 {
+                return Ok(Received::Timeout);
+            }
+            let header = unsafe { &(*self.block_desc(self.block)).hdr.bh1 };
+            if header.num_pkts == 0 {
+                self.release_block();
+                return Ok(Received::Timeout);
+            }
+            self.packet = Some((header.offset_to_first_pkt as usize, header.num_pkts));
+        }
+
+        let (offset, remaining) = self.packet.unwrap();
+        let block = unsafe { self.ring.add(self.block * self.block_size) };
+        let frame = unsafe {
+            let header = &*(block.add(offset) as *const libc::tpacket3_hdr);
+            let caplen = header.tp_snaplen.min(self.snaplen);
+            let data = std::slice::from_raw_parts(block.add(offset + header.tp_mac as usize), caplen as usize);
+            let (data, extra) = self.restore_vlan_tag(header, data);
+            let frame = Frame {
+                timestamp: Duration::new(u64::from(header.tp_sec), header.tp_nsec),
+                caplen: caplen + extra,
+                len: header.tp_len + extra,
+                interface: 0,
+                link_type: self.link_type,
+                data
+            };
+            self.packet = Some((offset + header.tp_next_offset as usize, remaining - 1));
+            frame
+        };
+
+        if remaining == 1 {
+            self.release_block();
+        }
+        Ok(Received::Frame(frame))
+    }
+
+    fn detach_filter(&self) {
+        let unused = 0 as libc::c_int;
+        // Fails with ENOENT when no filter was attached, which is fine.
+        unsafe { libc::setsockopt(self.fd, libc::SOL_SOCKET, libc::SO_DETACH_FILTER, &unused as *const libc::c_int as *const libc::c_void, mem::size_of::<libc::c_int>() as libc::socklen_t) };
+    }
+}
+
+impl CaptureSource for AfPacketSource {
+    fn next_frame(&mut self) -> Result<Received, CaptureError> {
+        loop {
+            match self.read_frame()? {
+                Received::Frame(frame) if !matches(&self.user_filter, &frame) => {}
+                received => return Ok(received)
+            }
+        }
+    }
+
+    fn link_type(&self) -> u16 {
+        self.link_type
+    }
+
+    fn interfaces(&self) -> Vec<SourceInterface> {
+        vec![SourceInterface { name: Some(self.name.clone()), link_type: self.link_type }]
+    }
+
+    // The kernel only sees frames without their VLAN tag, so filters on VLANs
+    // can't be attached to the socket and are run on the restored frames.
+    fn set_filter(&mut self, expression: &str) -> Result<(), CaptureError> {
+        let vlan = expression.split(|c: char| !c.is_ascii_alphanumeric()).any(|word| word == "vlan");
+        if expression.is_empty() || vlan {
+            self.user_filter = OfflineFilter::compile(expression, self.link_type)?;
+            self.detach_filter();
+            return Ok(());
+        }
+
+        let program = Capture::dead(Linktype(i32::from(self.link_type)))?
+            .compile(expression, true)
+            .map_err(|e| CaptureError::InvalidFilter(expression.to_string(), e))?;
+        let instructions = program.get_instructions();
+        // `BpfInstruction` is a transparent wrapper around the kernel's `sock_filter`.
+        let fprog = libc::sock_fprog {
+            len: instructions.len() as u16,
+            filter: instructions.as_ptr() as *mut libc::sock_filter
+        };
+        // Attaching replaces the previous filter without a window where none applies.
+        unsafe { set_option(self.fd, libc::SOL_SOCKET, libc::SO_ATTACH_FILTER, &fprog)? };
+        self.user_filter = None;
+        Ok(())
+    }
+
+    // The kernel resets its counters on every read, so they are accumulated here.
+    fn stats(&mut self) -> Option<Stat> {
+        let mut stats: libc::tpacket_stats_v3 = unsafe { mem::zeroed() };
+        let mut length = mem::size_of::<libc::tpacket_stats_v3>() as libc::socklen_t;
+        let result = unsafe {
+            libc::getsockopt(self.fd, libc::SOL_PACKET, libc::PACKET_STATISTICS, &mut stats as *mut libc::tpacket_stats_v3 as *mut libc::c_void, &mut length)
+        };
+        if result < 0 {
+            return None;
+        }
+        self.stats.received = self.stats.received.wrapping_add(stats.tp_packets);
+        self.stats.dropped = self.stats.dropped.wrapping_add(stats.tp_drops);
+        Some(self.stats)
+    }
+}
+
+impl Drop for AfPacketSource {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.ring.is_null() {
+                libc::munmap(self.ring as *mut libc::c_void, self.ring_len);
+            }
+            libc::close(self.fd);
+        }
+    }
+}
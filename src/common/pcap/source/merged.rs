@@ -0,0 +1,276 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use pcap::Stat;
+
+use crate::common::network::datalink::LINKTYPE_ETHERNET;
+use crate::common::pcap::source::{CaptureSource, Frame, Received, SourceInterface};
+use crate::common::pcap::stats::STATS_INTERVAL;
+use crate::common::pcap::CaptureError;
+
+const QUEUE_CAPACITY: usize = 1024;
+// How long a live source may hold on to frames before handing them over, as
+// libpcap does with its read timeout when not in immediate mode.
+const DEFAULT_LAG: Duration = Duration::from_secs(1);
+
+enum Event {
+    Frame(Frame),
+    // The input timed out and won't deliver anything captured before the
+    // watermark, given as a capture timestamp.
+    Idle(Duration),
+    Closed,
+    Failed(CaptureError)
+}
+
+#[derive(Default)]
+struct Control {
+    filter: Mutex<Option<String>>,
+    stats: Mutex<Option<Stat>>
+}
+
+struct Input {
+    frames: VecDeque<Frame>,
+    watermark: Option<Duration>,
+    closed: bool,
+    control: Arc<Control>
+}
+
+// Reads several sources at once, each on its own thread, and hands their
+// frames out in timestamp order. A frame is only released once every other
+// input has queued a frame, closed, or timed out with a watermark past it.
+// Watermarks trail the wall clock by `lag`, so live sources that buffer frames
+// for longer than that can still deliver them out of order.
+pub struct MergedSource {
+    interfaces: Vec<SourceInterface>,
+    inputs: Vec<Input>,
+    // Sources wait here until the first read so filters apply from the start.
+    pending: Vec<(u32, Box<dyn CaptureSource>)>,
+    receiver: Receiver<(usize, Event)>,
+    sender: Option<SyncSender<(usize, Event)>>,
+    timeout: Duration,
+    lag: Duration,
+    running: Arc<AtomicBool>,
+    workers: Vec<JoinHandle<()>>
+}
+
+impl MergedSource {
+    pub fn new(sources: Vec<Box<dyn CaptureSource>>) -> MergedSource {
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_CAPACITY);
+        let mut interfaces = vec![];
+        let mut pending = vec![];
+
+        for source in sources {
+            pending.push((interfaces.len() as u32, source));
+            interfaces.extend(pending.last().unwrap().1.interfaces());
+        }
+
+        MergedSource {
+            interfaces,
+            inputs: vec![],
+            pending,
+            receiver,
+            sender: Some(sender),
+            timeout: Duration::from_millis(100),
+            lag: DEFAULT_LAG,
+            running: Arc::new(AtomicBool::new(true)),
+            workers: vec![]
+        }
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> MergedSource {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn lag(mut self, lag: Duration) -> MergedSource {
+        self.lag = lag;
+        self
+    }
+
+    fn start(&mut self) {
+        let Some(sender) = self.sender.take() else {
+            return;
+        };
+
+        for (index, (offset, source)) in self.pending.drain(..).enumerate() {
+            let control = Arc::new(Control::default());
+            self.inputs.push(Input {
+                frames: VecDeque::new(),
+                watermark: None,
+                closed: false,
+                control: control.clone()
+            });
+
+            let (lag, running, sender) = (self.lag, self.running.clone(), sender.clone());
+            self.workers.push(thread::spawn(move || read(index, offset, lag, source, control, running, sender)));
+        }
+    }
+
+    fn receive(&mut self, index: usize, event: Event) -> Result<(), CaptureError> {
+        let input = &mut self.inputs[index];
+        match event {
+            Event::Frame(frame) => {
+                input.frames.push_back(frame);
+                input.watermark = None;
+            }
+            Event::Idle(watermark) => {
+                input.watermark = Some(watermark);
+            }
+            Event::Closed => {
+                input.closed = true;
+            }
+            Event::Failed(e) => {
+                input.closed = true;
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    fn pop_oldest(&mut self) -> Option<Frame> {
+        let (timestamp, index) = self.inputs.iter()
+            .enumerate()
+            .filter_map(|(index, input)| Some((input.frames.front()?.timestamp, index)))
+            .min()?;
+
+        let released = self.inputs.iter()
+            .filter(|input| input.frames.is_empty() && !input.closed)
+            .all(|input| input.watermark.is_some_and(|watermark| timestamp <= watermark));
+        if released { self.inputs[index].frames.pop_front() } else { None }
+    }
+}
+
+fn read(index: usize, offset: u32, lag: Duration, mut source: Box<dyn CaptureSource>, control: Arc<Control>, running: Arc<AtomicBool>, sender: SyncSender<(usize, Event)>) {
+    let mut stats_refreshed: Option<Instant> = None;
+
+    while running.load(Ordering::Acquire) {
+        if let Some(expression) = control.filter.lock().unwrap().take() {
+            if let Err(e) = source.set_filter(&expression) {
+                let _ = sender.send((index, Event::Failed(e)));
+                break;
+            }
+        }
+        if stats_refreshed.is_none_or(|refreshed| refreshed.elapsed() >= STATS_INTERVAL) {
+            *control.stats.lock().unwrap() = source.stats();
+            stats_refreshed = Some(Instant::now());
+        }
+
+        let event = match source.next_frame() {
+            Ok(Received::Frame(mut frame)) => {
+                frame.interface += offset;
+                Event::Frame(frame)
+            }
+            Ok(Received::Timeout) => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+                Event::Idle(now.saturating_sub(lag))
+            }
+            Ok(Received::Closed) => Event::Closed,
+            Err(e) => Event::Failed(e)
+        };
+        let last = matches!(event, Event::Closed | Event::Failed(_));
+        if sender.send((index, event)).is_err() || last {
+            break;
+        }
+    }
+    *control.stats.lock().unwrap() = source.stats();
+}
+
+impl CaptureSource for MergedSource {
+    fn next_frame(&mut self) -> Result<Received, CaptureError> {
+        self.start();
+        let deadline = Instant::now() + self.timeout;
+
+        loop {
+            if let Some(frame) = self.pop_oldest() {
+                return Ok(Received::Frame(frame));
+            }
+            if self.inputs.iter().all(|input| input.closed) {
+                return Ok(Received::Closed);
+            }
+
+            match self.receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok((index, event)) => self.receive(index, event)?,
+                Err(RecvTimeoutError::Timeout) => return Ok(Received::Timeout),
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.inputs.iter_mut().for_each(|input| input.closed = true);
+                }
+            }
+        }
+    }
+
+    // Filters are compiled for the first interface's link type.
+    fn link_type(&self) -> u16 {
+        self.interfaces.first().map_or(LINKTYPE_ETHERNET, |interface| interface.link_type)
+    }
+
+    fn interfaces(&self) -> Vec<SourceInterface> {
+        self.interfaces.clone()
+    }
+
+    fn set_filter(&mut self, expression: &str) -> Result<(), CaptureError> {
+        for (_, source) in &mut self.pending {
+            source.set_filter(expression)?;
+        }
+        for input in &self.inputs {
+            *input.control.filter.lock().unwrap() = Some(expression.to_string());
+        }
+        Ok(())
+    }
+
+    fn stats(&mut self) -> Option<Stat> {
+        self.inputs.iter()
+            .filter_map(|input| *input.control.stats.lock().unwrap())
+            .reduce(|total, stat| Stat {
+                received: total.received.wrapping_add(stat.received),
+                dropped: total.dropped.wrapping_add(stat.dropped),
+                if_dropped: total.if_dropped.wrapping_add(stat.if_dropped)
+            })
+    }
+}
+
+impl Drop for MergedSource {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Release);
+        self.sender = None;
+        // Draining unblocks workers stuck on a full queue; it ends once all of them exit.
+        while self.receiver.recv().is_ok() {}
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::common::fixtures::ethernet_udp;
+    use crate::common::pcap::source::merged::MergedSource;
+    use crate::common::pcap::source::{CaptureSource, Frame, MemorySource};
+    use crate::common::pcap::Sniffer;
+
+    fn source(seconds: &[u64]) -> Box<dyn CaptureSource> {
+        let frames = seconds.iter()
+            .map(|&second| Frame::new(1, Duration::from_secs(second), ethernet_udp([10, 0, 0, 1], [10, 0, 0, 2], 1, 2, &[])))
+            .collect();
+        Box::new(MemorySource::from_frames(1, frames))
+    }
+
+    #[test]
+    fn merges_sources_in_timestamp_order() {
+        let merged = MergedSource::new(vec![source(&[1, 4, 5, 9]), source(&[2, 3, 8]), source(&[])]);
+        let sniffer = Sniffer::builder().source(merged).build().unwrap();
+
+        let mut received = vec![];
+        let stats = sniffer.sniff(|packet| {
+            received.push((packet.timestamp.as_secs(), packet.interface));
+            false
+        }).unwrap();
+        assert_eq!(received, [(1, 0), (2, 1), (3, 1), (4, 0), (5, 0), (8, 1), (9, 0)]);
+        assert_eq!(stats.captured, 7);
+    }
+}
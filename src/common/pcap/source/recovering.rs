@@ -0,0 +1,175 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use pcap::Stat;
+
+use crate::common::pcap::recovery::{classify, InterfaceFault, LifecycleEvent, LifecycleHook, RecoveryPolicy};
+use crate::common::pcap::source::{CaptureSource, Received, SourceInterface};
+use crate::common::pcap::CaptureError;
+
+const NO_STATS: Stat = Stat { received: 0, dropped: 0, if_dropped: 0 };
+
+pub type SourceFactory = Box<dyn FnMut() -> Result<Box<dyn CaptureSource>, CaptureError> + Send>;
+
+// Wraps a live source and, when its interface goes away, reopens it through
+// `open` as the policy allows.
+pub struct RecoveringSource {
+    interface: String,
+    open: SourceFactory,
+    policy: RecoveryPolicy,
+    events: Option<LifecycleHook>,
+    source: Option<Box<dyn CaptureSource>>,
+    link_type: u16,
+    interfaces: Vec<SourceInterface>,
+    filter: Option<String>,
+    lost: Option<(InterfaceFault, String)>,
+    attempt: u32,
+    retry_at: Instant,
+    timeout: Duration,
+    // Counters of closed sources, which start from zero again when reopened.
+    carried: Stat,
+    last: Option<Stat>
+}
+
+impl RecoveringSource {
+    pub fn open(interface: impl Into<String>, mut open: SourceFactory, policy: RecoveryPolicy, events: Option<LifecycleHook>) -> Result<RecoveringSource, CaptureError> {
+        let source = open().map_err(classify)?;
+        let recovering = RecoveringSource {
+            interface: interface.into(),
+            open,
+            policy,
+            events,
+            link_type: source.link_type(),
+            interfaces: source.interfaces(),
+            source: Some(source),
+            filter: None,
+            lost: None,
+            attempt: 0,
+            retry_at: Instant::now(),
+            timeout: Duration::from_millis(100),
+            carried: NO_STATS,
+            last: None
+        };
+        recovering.emit(LifecycleEvent::Opened { interface: recovering.interface.clone() });
+        Ok(recovering)
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> RecoveringSource {
+        self.timeout = timeout;
+        self
+    }
+
+    fn emit(&self, event: LifecycleEvent) {
+        if let Some(events) = &self.events {
+            events(&event);
+        }
+    }
+
+    fn lose(&mut self, fault: InterfaceFault, message: String) -> Result<Received, CaptureError> {
+        self.source = None;
+        if let Some(last) = self.last.take() {
+            self.carried = add(self.carried, last);
+        }
+        self.emit(LifecycleEvent::Lost { interface: self.interface.clone(), fault, message: message.clone() });
+
+        self.lost = Some((fault, message));
+        self.retry(1)
+    }
+
+    fn retry(&mut self, attempt: u32) -> Result<Received, CaptureError> {
+        let (fault, message) = self.lost.clone().unwrap_or((InterfaceFault::Removed, String::new()));
+
+        let backoff = match fault {
+            InterfaceFault::PermissionDenied => None,
+            InterfaceFault::Down | InterfaceFault::Removed => self.policy.backoff(attempt)
+        };
+        match backoff {
+            Some(delay) => {
+                self.attempt = attempt;
+                self.retry_at = Instant::now() + delay;
+                self.emit(LifecycleEvent::Reopening { interface: self.interface.clone(), attempt, delay });
+                Ok(Received::Timeout)
+            }
+            None => {
+                if attempt > 1 {
+                    self.emit(LifecycleEvent::GaveUp { interface: self.interface.clone(), attempts: attempt - 1 });
+                }
+                Err(CaptureError::InterfaceFault(fault, message))
+            }
+        }
+    }
+
+    fn reopen(&mut self) -> Result<Received, CaptureError> {
+        let wait = self.retry_at.saturating_duration_since(Instant::now());
+        if !wait.is_zero() {
+            thread::sleep(wait.min(self.timeout));
+            return Ok(Received::Timeout);
+        }
+
+        match (self.open)().map_err(classify) {
+            Ok(mut source) => {
+                if let Some(expression) = &self.filter {
+                    source.set_filter(expression)?;
+                }
+                self.source = Some(source);
+                self.lost = None;
+                self.emit(LifecycleEvent::Reopened { interface: self.interface.clone(), attempts: self.attempt });
+                self.attempt = 0;
+                Ok(Received::Timeout)
+            }
+            Err(CaptureError::InterfaceFault(fault, message)) => {
+                self.lost = Some((fault, message));
+                self.retry(self.attempt + 1)
+            }
+            Err(e) => Err(e)
+        }
+    }
+}
+
+fn add(a: Stat, b: Stat) -> Stat {
+    Stat {
+        received: a.received.wrapping_add(b.received),
+        dropped: a.dropped.wrapping_add(b.dropped),
+        if_dropped: a.if_dropped.wrapping_add(b.if_dropped)
+    }
+}
+
+impl CaptureSource for RecoveringSource {
+    fn next_frame(&mut self) -> Result<Received, CaptureError> {
+        let Some(source) = &mut self.source else {
+            return self.reopen();
+        };
+
+        match source.next_frame().map_err(classify) {
+            Err(CaptureError::InterfaceFault(fault, message)) => self.lose(fault, message),
+            received => received
+        }
+    }
+
+    fn link_type(&self) -> u16 {
+        self.link_type
+    }
+
+    fn interfaces(&self) -> Vec<SourceInterface> {
+        self.interfaces.clone()
+    }
+
+    fn set_filter(&mut self, expression: &str) -> Result<(), CaptureError> {
+        self.filter = if expression.is_empty() { None } else { Some(expression.to_string()) };
+        match &mut self.source {
+            Some(source) => source.set_filter(expression),
+            None => Ok(())
+        }
+    }
+
+    fn stats(&mut self) -> Option<Stat> {
+        if let Some(source) = &mut self.source {
+            self.last = source.stats().or(self.last);
+        }
+        match self.last {
+            Some(last) => Some(add(self.carried, last)),
+            None if self.carried != NO_STATS => Some(self.carried),
+            None => None
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use pcap::Stat;
+
+use crate::common::pcap::clock::ReplaySpeed;
+use crate::common::pcap::source::{CaptureSource, Frame, Received, SourceInterface};
+use crate::common::pcap::CaptureError;
+
+// Holds frames back until they are due relative to the first one, scaled by
+// the replay speed.
+pub struct ReplaySource {
+    source: Box<dyn CaptureSource>,
+    speed: ReplaySpeed,
+    timeout: Duration,
+    origin: Option<(Duration, Instant)>,
+    pending: Option<Frame>
+}
+
+impl ReplaySource {
+    pub fn new(source: Box<dyn CaptureSource>, speed: ReplaySpeed) -> ReplaySource {
+        ReplaySource {
+            source,
+            speed,
+            timeout: Duration::from_millis(100),
+            origin: None,
+            pending: None
+        }
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> ReplaySource {
+        self.timeout = timeout;
+        self
+    }
+
+    fn due(&mut self, timestamp: Duration) -> Option<Instant> {
+        let speed = self.speed.factor()?;
+        let (first, started) = *self.origin.get_or_insert((timestamp, Instant::now()));
+        // Frames older than the first one go out right away.
+        Some(started + timestamp.saturating_sub(first).div_f64(speed))
+    }
+}
+
+impl CaptureSource for ReplaySource {
+    fn next_frame(&mut self) -> Result<Received, CaptureError> {
+        let frame = match self.pending.take() {
+            Some(frame) => frame,
+            None => match self.source.next_frame()? {
+                Received::Frame(frame) => frame,
+                received => return Ok(received)
+            }
+        };
+
+        if let Some(due) = self.due(frame.timestamp) {
+            let wait = due.saturating_duration_since(Instant::now());
+            if wait > self.timeout {
+                thread::sleep(self.timeout);
+                self.pending = Some(frame);
+                return Ok(Received::Timeout);
+            }
+            thread::sleep(wait);
+        }
+        Ok(Received::Frame(frame))
+    }
+
+    fn link_type(&self) -> u16 {
+        self.source.link_type()
+    }
+
+    fn interfaces(&self) -> Vec<SourceInterface> {
+        self.source.interfaces()
+    }
+
+    fn set_filter(&mut self, expression: &str) -> Result<(), CaptureError> {
+        self.source.set_filter(expression)
+    }
+
+    fn stats(&mut self) -> Option<Stat> {
+        self.source.stats()
+    }
+}
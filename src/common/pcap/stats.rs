@@ -0,0 +1,198 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use pcap::Stat;
+use serde::Serialize;
+
+use crate::common::network::ReadError;
+use crate::common::pcap::limits::Limit;
+use crate::common::pcap::Verdict;
+
+// How often kernel counters are read during a capture.
+pub(in crate::common::pcap) const STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Statistics {
+    pub received: u32,
+    pub dropped: u32,
+    pub if_dropped: u32,
+    pub captured: u64,
+    // Captured bytes, which is less than on the wire for truncated frames.
+    pub bytes: u64,
+    pub decoded: u64,
+    pub rejected: u64,
+    pub decode_errors: BTreeMap<&'static str, u64>,
+    // Decoded frames by their outermost VLAN ID.
+    pub vlans: BTreeMap<u16, u64>,
+    pub queues: Vec<QueueStatistics>,
+    pub limit_reached: Option<Limit>
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct QueueStatistics {
+    pub name: String,
+    pub enqueued: u64,
+    pub dropped: u64,
+    pub processed: u64
+}
+
+impl Statistics {
+    pub fn decode_failures(&self) -> u64 {
+        self.decode_errors.values().sum()
+    }
+}
+
+// Counters bumped for every frame. Each pipeline worker gets its own set so
+// that workers never contend, and `snapshot` adds them all up.
+#[derive(Default)]
+pub(in crate::common::pcap) struct Counters {
+    captured: AtomicU64,
+    bytes: AtomicU64,
+    decoded: AtomicU64,
+    rejected: AtomicU64,
+    // Only locked for tagged or undecodable frames, and uncontended per worker.
+    vlans: Mutex<BTreeMap<u16, u64>>,
+    decode_errors: Mutex<BTreeMap<&'static str, u64>>
+}
+
+impl Counters {
+    pub(in crate::common::pcap) fn captured(&self, bytes: usize) {
+        self.captured.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(in crate::common::pcap) fn decoded(&self, vlan: Option<u16>) {
+        self.decoded.fetch_add(1, Ordering::Relaxed);
+        if let Some(vlan) = vlan {
+            *self.vlans.lock().unwrap().entry(vlan).or_default() += 1;
+        }
+    }
+
+    pub(in crate::common::pcap) fn verdict(&self, verdict: Option<Verdict>) {
+        if let Some(Verdict::Reject) = verdict {
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(in crate::common::pcap) fn decode_error(&self, error: &ReadError) {
+        *self.decode_errors.lock().unwrap().entry(error.name()).or_default() += 1;
+    }
+
+    fn add_to(&self, stats: &mut Statistics) {
+        stats.captured += self.captured.load(Ordering::Relaxed);
+        stats.bytes += self.bytes.load(Ordering::Relaxed);
+        stats.decoded += self.decoded.load(Ordering::Relaxed);
+        stats.rejected += self.rejected.load(Ordering::Relaxed);
+        for (vlan, count) in self.vlans.lock().unwrap().iter() {
+            *stats.vlans.entry(*vlan).or_default() += count;
+        }
+        for (name, count) in self.decode_errors.lock().unwrap().iter() {
+            *stats.decode_errors.entry(name).or_default() += count;
+        }
+    }
+
+    fn reset(&self) {
+        for counter in [&self.captured, &self.bytes, &self.decoded, &self.rejected] {
+            counter.store(0, Ordering::Relaxed);
+        }
+        self.vlans.lock().unwrap().clear();
+        self.decode_errors.lock().unwrap().clear();
+    }
+}
+
+pub(in crate::common::pcap) struct QueueCounters {
+    name: String,
+    enqueued: AtomicU64,
+    dropped: AtomicU64,
+    processed: AtomicU64
+}
+
+impl QueueCounters {
+    pub(in crate::common::pcap) fn sent(&self, sent: bool) {
+        let counter = if sent { &self.enqueued } else { &self.dropped };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(in crate::common::pcap) fn processed(&self) {
+        self.processed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> QueueStatistics {
+        QueueStatistics {
+            name: self.name.clone(),
+            enqueued: self.enqueued.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            processed: self.processed.load(Ordering::Relaxed)
+        }
+    }
+}
+
+// Counters registered by a session. Resetting for a new session drops them
+// from the snapshot, while an older session keeps updating its own.
+#[derive(Default)]
+struct Shards {
+    workers: Vec<Arc<Counters>>,
+    queues: Vec<Arc<QueueCounters>>
+}
+
+#[derive(Clone, Default)]
+pub struct StatsHandle {
+    inner: Arc<Mutex<Statistics>>,
+    counters: Arc<Counters>,
+    shards: Arc<Mutex<Shards>>
+}
+
+impl StatsHandle {
+    pub fn snapshot(&self) -> Statistics {
+        let mut stats = self.inner.lock().unwrap().clone();
+        self.counters.add_to(&mut stats);
+
+        let shards = self.shards.lock().unwrap();
+        for worker in &shards.workers {
+            worker.add_to(&mut stats);
+        }
+        stats.queues = shards.queues.iter().map(|queue| queue.snapshot()).collect();
+        stats
+    }
+
+    pub(in crate::common::pcap) fn update(&self, f: impl FnOnce(&mut Statistics)) {
+        f(&mut self.inner.lock().unwrap());
+    }
+
+    pub(in crate::common::pcap) fn counters(&self) -> &Counters {
+        &self.counters
+    }
+
+    pub(in crate::common::pcap) fn worker(&self) -> Arc<Counters> {
+        let counters = Arc::new(Counters::default());
+        self.shards.lock().unwrap().workers.push(counters.clone());
+        counters
+    }
+
+    pub(in crate::common::pcap) fn queue(&self, name: impl Into<String>) -> Arc<QueueCounters> {
+        let queue = Arc::new(QueueCounters {
+            name: name.into(),
+            enqueued: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            processed: AtomicU64::new(0)
+        });
+        self.shards.lock().unwrap().queues.push(queue.clone());
+        queue
+    }
+
+    pub(in crate::common::pcap) fn reset(&self) {
+        self.update(|stats| *stats = Statistics::default());
+        self.counters.reset();
+        *self.shards.lock().unwrap() = Shards::default();
+    }
+
+    pub(in crate::common::pcap) fn set_pcap(&self, stat: Stat) {
+        self.update(|stats| {
+            stats.received = stat.received;
+            stats.dropped = stat.dropped;
+            stats.if_dropped = stat.if_dropped;
+        });
+    }
+}
@@ -0,0 +1,24 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+// Stopping only flips an atomic flag, so it is safe to call from signal handlers.
+#[derive(Clone, Default)]
+pub struct StopHandle {
+    stopped: Arc<AtomicBool>
+}
+
+impl StopHandle {
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Release);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Acquire)
+    }
+
+    // A stop only ends the captures running when it is requested, so every
+    // new session starts out unstopped.
+    pub(in crate::common::pcap) fn reset(&self) {
+        self.stopped.store(false, Ordering::Release);
+    }
+}
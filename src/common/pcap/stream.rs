@@ -0,0 +1,61 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::thread;
+
+use futures_core::Stream;
+use tokio::sync::mpsc;
+
+use crate::common::pcap::captured::CapturedPacket;
+use crate::common::pcap::session::{Packets, Session};
+use crate::common::pcap::stop::StopHandle;
+use crate::common::pcap::{CaptureError, Sniffer};
+
+pub struct PacketStream {
+    receiver: mpsc::Receiver<Result<CapturedPacket, CaptureError>>,
+    cancel: StopHandle
+}
+
+impl Stream for PacketStream {
+    type Item = Result<CapturedPacket, CaptureError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+impl Drop for PacketStream {
+    fn drop(&mut self) {
+        self.cancel.stop();
+    }
+}
+
+impl Sniffer {
+    // Capturing blocks, so it runs on its own thread. Dropping the stream cancels
+    // it, which the thread notices at the next frame or read timeout.
+    pub fn stream(&self, buffer: usize) -> PacketStream {
+        let sniffer = self.clone();
+        let cancel = StopHandle::default();
+        let (sender, receiver) = mpsc::channel(buffer.max(1));
+
+        let session_cancel = cancel.clone();
+        thread::spawn(move || {
+            match Session::open(&sniffer).map(|session| Packets::new(session.cancel_on(session_cancel))) {
+                Ok(packets) => {
+                    for packet in packets {
+                        if sender.blocking_send(packet).is_err() {
+                            break;
+                        }
+                    }
+                }
+                Err(e) => {
+                    let _ = sender.blocking_send(Err(e));
+                }
+            }
+        });
+
+        PacketStream {
+            receiver,
+            cancel
+        }
+    }
+}
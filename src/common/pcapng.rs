@@ -0,0 +1,310 @@
+use std::array::TryFromSliceError;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufReader, ErrorKind, Read};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::common::network::packet::Packet;
+use crate::common::network::ReadError;
+use crate::common::pcapng::block::{Block, ByteOrder, InterfaceDescription, NameRecord, SectionHeader};
+
+pub mod block;
+pub mod writer;
+
+const MAX_BLOCK_SIZE: u32 = 64 * 1024 * 1024;
+// Type, lengths, magic, versions and section length.
+const MIN_SECTION_HEADER_SIZE: u32 = 28;
+
+#[derive(Debug)]
+pub enum PcapNgError {
+    Io(std::io::Error),
+    InvalidByteOrder,
+    MissingSectionHeader,
+    InvalidBlockLength(u32),
+    TruncatedBlock,
+    UnknownInterface(u32)
+}
+
+impl Display for PcapNgError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PcapNgError::Io(e) => {
+                write!(f, "Couldn't read pcapng data: {}.", e)
+            }
+            PcapNgError::InvalidByteOrder => {
+                write!(f, "Section header has an invalid byte-order magic.")
+            }
+            PcapNgError::MissingSectionHeader => {
+                write!(f, "Stream doesn't start with a section header block.")
+            }
+            PcapNgError::InvalidBlockLength(n) => {
+                write!(f, "Block total length {} is invalid.", n)
+            }
+            PcapNgError::TruncatedBlock => {
+                write!(f, "Block body is shorter than its fields require.")
+            }
+            PcapNgError::UnknownInterface(n) => {
+                write!(f, "Packet refers to interface {} which wasn't described.", n)
+            }
+        }
+    }
+}
+
+impl Error for PcapNgError {}
+
+impl From<std::io::Error> for PcapNgError {
+    fn from(e: std::io::Error) -> Self {
+        PcapNgError::Io(e)
+    }
+}
+
+impl From<TryFromSliceError> for PcapNgError {
+    fn from(_: TryFromSliceError) -> Self {
+        PcapNgError::TruncatedBlock
+    }
+}
+
+pub struct PcapNgPacket {
+    pub interface_id: u32,
+    pub interface: Arc<InterfaceDescription>,
+    pub timestamp: Option<Duration>,
+    pub captured_len: u32,
+    pub original_len: u32,
+    pub flags: Option<u32>,
+    pub comments: Vec<String>,
+    pub data: Vec<u8>,
+    pub packet: Result<Packet, ReadError>
+}
+
+impl PcapNgPacket {
+    fn new(interface_id: u32, interface: Arc<InterfaceDescription>, data: Vec<u8>) -> PcapNgPacket {
+        let packet = Packet::from_bytes(interface.link_type, &data);
+
+        PcapNgPacket {
+            interface_id,
+            interface,
+            timestamp: None,
+            captured_len: data.len() as u32,
+            original_len: data.len() as u32,
+            flags: None,
+            comments: vec![],
+            data,
+            packet
+        }
+    }
+}
+
+pub struct PcapNgReader<R: Read> {
+    reader: R,
+    section: Option<SectionHeader>,
+    interfaces: Vec<Arc<InterfaceDescription>>,
+    names: Vec<NameRecord>
+}
+
+impl PcapNgReader<BufReader<File>> {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, PcapNgError> {
+        Ok(PcapNgReader::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: Read> PcapNgReader<R> {
+    pub fn new(reader: R) -> PcapNgReader<R> {
+        PcapNgReader {
+            reader,
+            section: None,
+            interfaces: vec![],
+            names: vec![]
+        }
+    }
+
+    pub fn section(&self) -> Option<&SectionHeader> {
+        self.section.as_ref()
+    }
+
+    pub fn interfaces(&self) -> &[Arc<InterfaceDescription>] {
+        &self.interfaces
+    }
+
+    pub fn interface(&self, id: u32) -> Option<&Arc<InterfaceDescription>> {
+        self.interfaces.get(id as usize)
+    }
+
+    pub fn names(&self) -> &[NameRecord] {
+        &self.names
+    }
+
+    pub fn next_block(&mut self) -> Result<Option<Block>, PcapNgError> {
+        let mut header = [0u8; 8];
+        if !self.read_or_eof(&mut header)? {
+            return Ok(None);
+        }
+
+        if u32::from_le_bytes(header[..4].try_into()?) == block::SECTION_HEADER {
+            return self.read_section_header(&header);
+        }
+
+        let byte_order = self.section.as_ref().ok_or(PcapNgError::MissingSectionHeader)?.byte_order;
+        let block_type = byte_order.u32(&header[..4]);
+        let body = self.read_body(byte_order.u32(&header[4..8]), 0)?;
+        self.finish_block(block_type, &body, byte_order)
+    }
+
+    // The byte order of a section is only known after reading the magic that
+    // follows the block type, so the total length has to wait until then.
+    fn read_section_header(&mut self, header: &[u8; 8]) -> Result<Option<Block>, PcapNgError> {
+        let mut magic = [0u8; 4];
+        self.reader.read_exact(&mut magic)?;
+        let byte_order = ByteOrder::from_magic(magic).ok_or(PcapNgError::InvalidByteOrder)?;
+        let total_length = byte_order.u32(&header[4..8]);
+        if total_length < MIN_SECTION_HEADER_SIZE {
+            return Err(PcapNgError::InvalidBlockLength(total_length));
+        }
+        self.section = None;
+        self.interfaces.clear();
+        self.names.clear();
+
+        let mut body = magic.to_vec();
+        body.extend(self.read_body(total_length, 4)?);
+        self.finish_block(block::SECTION_HEADER, &body, byte_order)
+    }
+
+    fn finish_block(&mut self, block_type: u32, body: &[u8], byte_order: ByteOrder) -> Result<Option<Block>, PcapNgError> {
+        let block = Block::new(block_type, body, byte_order)?;
+        match &block {
+            Block::SectionHeader(section) => self.section = Some(section.clone()),
+            Block::InterfaceDescription(interface) => self.interfaces.push(Arc::new(interface.clone())),
+            Block::NameResolution(records) => self.names.extend(records.iter().cloned()),
+            _ => {}
+        }
+        Ok(Some(block))
+    }
+
+    // Reads the rest of a block whose first 8 + `consumed` bytes were already read,
+    // dropping the trailing copy of the total length.
+    fn read_body(&mut self, total_length: u32, consumed: usize) -> Result<Vec<u8>, PcapNgError> {
+        if (total_length as usize) < 12 + consumed || !total_length.is_multiple_of(4) || total_length > MAX_BLOCK_SIZE {
+            return Err(PcapNgError::InvalidBlockLength(total_length));
+        }
+
+        let mut body = vec![0u8; total_length as usize - 8 - consumed];
+        self.reader.read_exact(&mut body)?;
+        body.truncate(body.len() - 4);
+        Ok(body)
+    }
+
+    fn read_or_eof(&mut self, buf: &mut [u8]) -> Result<bool, PcapNgError> {
+        let mut read = 0;
+        while read < buf.len() {
+            match self.reader.read(&mut buf[read..]) {
+                Ok(0) if read == 0 => return Ok(false),
+                Ok(0) => return Err(PcapNgError::Io(ErrorKind::UnexpectedEof.into())),
+                Ok(n) => read += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into())
+            }
+        }
+        Ok(true)
+    }
+
+    fn next_packet(&mut self) -> Result<Option<PcapNgPacket>, PcapNgError> {
+        loop {
+            match self.next_block()? {
+                Some(Block::EnhancedPacket(epb)) => {
+                    let interface = self.interface(epb.interface_id)
+                        .ok_or(PcapNgError::UnknownInterface(epb.interface_id))?.clone();
+                    let timestamp = interface.timestamp(epb.timestamp);
+
+                    let mut packet = PcapNgPacket::new(epb.interface_id, interface, epb.data);
+                    packet.timestamp = Some(timestamp);
+                    packet.captured_len = epb.captured_len;
+                    packet.original_len = epb.original_len;
+                    packet.flags = epb.flags;
+                    packet.comments = epb.comments;
+                    return Ok(Some(packet));
+                }
+                Some(Block::SimplePacket(spb)) => {
+                    let interface = self.interface(0).ok_or(PcapNgError::UnknownInterface(0))?.clone();
+
+                    let mut packet = PcapNgPacket::new(0, interface, spb.data);
+                    packet.original_len = spb.original_len;
+                    return Ok(Some(packet));
+                }
+                Some(_) => {}
+                None => return Ok(None)
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for PcapNgReader<R> {
+    type Item = Result<PcapNgPacket, PcapNgError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_packet().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::time::Duration;
+
+    use crate::common::pcapng::writer::PcapNgWriter;
+    use crate::common::pcapng::{PcapNgError, PcapNgReader};
+
+    #[test]
+    fn rejects_short_section_header() {
+        let mut bytes = vec![];
+        bytes.extend(0x0A0D0D0Au32.to_le_bytes());
+        bytes.extend(12u32.to_le_bytes());
+        bytes.extend(0x1A2B3C4Du32.to_le_bytes());
+
+        let mut reader = PcapNgReader::new(Cursor::new(bytes));
+        assert!(matches!(reader.next_block(), Err(PcapNgError::InvalidBlockLength(12))));
+    }
+
+    #[test]
+    fn rejects_block_shorter_than_its_header() {
+        let mut writer = PcapNgWriter::new(vec![]).unwrap();
+        writer.add_interface(1, 65535, None).unwrap();
+        let mut bytes = writer.into_inner();
+        bytes.extend(6u32.to_le_bytes());
+        bytes.extend(8u32.to_le_bytes());
+
+        let mut reader = PcapNgReader::new(Cursor::new(bytes));
+        assert!(reader.next_block().is_ok());
+        assert!(reader.next_block().is_ok());
+        assert!(matches!(reader.next_block(), Err(PcapNgError::InvalidBlockLength(8))));
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut writer = PcapNgWriter::new(vec![]).unwrap();
+        writer.add_interface(1, 65535, Some("eth0")).unwrap();
+        writer.add_interface(113, 256, Some("any")).unwrap();
+        writer.write_packet(1, Duration::new(1_700_000_000, 123_456_789), 60, &[1, 2, 3, 4, 5], Some("hello")).unwrap();
+        writer.write_packet(0, Duration::from_secs(1), 14, &[0; 14], None).unwrap();
+
+        let mut reader = PcapNgReader::new(Cursor::new(writer.into_inner()));
+        let first = reader.next().unwrap().unwrap();
+        assert_eq!(first.interface_id, 1);
+        assert_eq!(first.interface.name.as_deref(), Some("any"));
+        assert_eq!(first.interface.link_type, 113);
+        assert_eq!(first.timestamp, Some(Duration::new(1_700_000_000, 123_456_789)));
+        assert_eq!((first.captured_len, first.original_len), (5, 60));
+        assert_eq!(first.data, [1, 2, 3, 4, 5]);
+        assert_eq!(first.comments, ["hello"]);
+
+        let second = reader.next().unwrap().unwrap();
+        assert_eq!(second.interface.name.as_deref(), Some("eth0"));
+        assert_eq!(second.timestamp, Some(Duration::from_secs(1)));
+        assert!(second.comments.is_empty());
+        assert!(reader.next().is_none());
+
+        assert_eq!(reader.interfaces().len(), 2);
+        assert_eq!(reader.section().unwrap().user_application.as_deref(), Some(env!("CARGO_PKG_NAME")));
+    }
+}
@@ -0,0 +1,323 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::common::pcapng::PcapNgError;
+
+pub const SECTION_HEADER: u32 = 0x0A0D0D0A;
+pub const INTERFACE_DESCRIPTION: u32 = 0x00000001;
+pub const SIMPLE_PACKET: u32 = 0x00000003;
+pub const NAME_RESOLUTION: u32 = 0x00000004;
+pub const ENHANCED_PACKET: u32 = 0x00000006;
+
+pub const BYTE_ORDER_MAGIC: u32 = 0x1A2B3C4D;
+
+const OPT_END: u16 = 0;
+const OPT_COMMENT: u16 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ByteOrder {
+    Big,
+    Little
+}
+
+impl ByteOrder {
+    pub fn from_magic(bytes: [u8; 4]) -> Option<ByteOrder> {
+        if u32::from_be_bytes(bytes) == BYTE_ORDER_MAGIC {
+            Some(ByteOrder::Big)
+        } else if u32::from_le_bytes(bytes) == BYTE_ORDER_MAGIC {
+            Some(ByteOrder::Little)
+        } else {
+            None
+        }
+    }
+
+    pub fn u16(self, bytes: &[u8]) -> u16 {
+        let bytes = [bytes[0], bytes[1]];
+        match self {
+            ByteOrder::Big => u16::from_be_bytes(bytes),
+            ByteOrder::Little => u16::from_le_bytes(bytes)
+        }
+    }
+
+    pub fn u32(self, bytes: &[u8]) -> u32 {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        match self {
+            ByteOrder::Big => u32::from_be_bytes(bytes),
+            ByteOrder::Little => u32::from_le_bytes(bytes)
+        }
+    }
+
+    pub fn u64(self, bytes: &[u8]) -> u64 {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]];
+        match self {
+            ByteOrder::Big => u64::from_be_bytes(bytes),
+            ByteOrder::Little => u64::from_le_bytes(bytes)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub enum Block {
+    SectionHeader(SectionHeader),
+    InterfaceDescription(InterfaceDescription),
+    EnhancedPacket(EnhancedPacket),
+    SimplePacket(SimplePacket),
+    NameResolution(Vec<NameRecord>),
+    Unknown {
+        block_type: u32,
+        body: Vec<u8>
+    }
+}
+
+impl Block {
+    pub fn new(block_type: u32, body: &[u8], byte_order: ByteOrder) -> Result<Block, PcapNgError> {
+        Ok(match block_type {
+            SECTION_HEADER => Block::SectionHeader(SectionHeader::new(body, byte_order)?),
+            INTERFACE_DESCRIPTION => Block::InterfaceDescription(InterfaceDescription::new(body, byte_order)?),
+            ENHANCED_PACKET => Block::EnhancedPacket(EnhancedPacket::new(body, byte_order)?),
+            SIMPLE_PACKET => Block::SimplePacket(SimplePacket::new(body, byte_order)?),
+            NAME_RESOLUTION => Block::NameResolution(NameRecord::list(body, byte_order)?),
+            block_type => Block::Unknown {
+                block_type,
+                body: body.to_vec()
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SectionHeader {
+    pub byte_order: ByteOrder,
+    pub major_version: u16,
+    pub minor_version: u16,
+    pub section_length: i64,
+    pub hardware: Option<String>,
+    pub os: Option<String>,
+    pub user_application: Option<String>,
+    pub comments: Vec<String>
+}
+
+impl SectionHeader {
+    const SIZE: usize = 16;
+
+    fn new(body: &[u8], byte_order: ByteOrder) -> Result<SectionHeader, PcapNgError> {
+        let bytes = slice(body, 0, Self::SIZE)?;
+        let mut header = SectionHeader {
+            byte_order,
+            major_version: byte_order.u16(&bytes[4..6]),
+            minor_version: byte_order.u16(&bytes[6..8]),
+            section_length: byte_order.u64(&bytes[8..16]) as i64,
+            hardware: None,
+            os: None,
+            user_application: None,
+            comments: vec![]
+        };
+
+        for (code, value) in options(&body[Self::SIZE..], byte_order)? {
+            match code {
+                OPT_COMMENT => header.comments.push(string(value)),
+                2 => header.hardware = Some(string(value)),
+                3 => header.os = Some(string(value)),
+                4 => header.user_application = Some(string(value)),
+                _ => {}
+            }
+        }
+        Ok(header)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InterfaceDescription {
+    pub link_type: u16,
+    pub snap_len: u32,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub ts_resolution: u8,
+    pub ts_offset: i64,
+    pub filter: Option<String>,
+    pub os: Option<String>,
+    pub comments: Vec<String>
+}
+
+impl InterfaceDescription {
+    const SIZE: usize = 8;
+    const DEFAULT_TS_RESOLUTION: u8 = 6;
+
+    fn new(body: &[u8], byte_order: ByteOrder) -> Result<InterfaceDescription, PcapNgError> {
+        let bytes = slice(body, 0, Self::SIZE)?;
+        let mut interface = InterfaceDescription {
+            link_type: byte_order.u16(&bytes[0..2]),
+            snap_len: byte_order.u32(&bytes[4..8]),
+            name: None,
+            description: None,
+            ts_resolution: Self::DEFAULT_TS_RESOLUTION,
+            ts_offset: 0,
+            filter: None,
+            os: None,
+            comments: vec![]
+        };
+
+        for (code, value) in options(&body[Self::SIZE..], byte_order)? {
+            match code {
+                OPT_COMMENT => interface.comments.push(string(value)),
+                2 => interface.name = Some(string(value)),
+                3 => interface.description = Some(string(value)),
+                9 if !value.is_empty() => interface.ts_resolution = value[0],
+                11 if !value.is_empty() => interface.filter = Some(string(&value[1..])),
+                12 => interface.os = Some(string(value)),
+                14 if value.len() >= 8 => interface.ts_offset = byte_order.u64(value) as i64,
+                _ => {}
+            }
+        }
+        Ok(interface)
+    }
+
+    // Timestamps are counted in units of 10^-n seconds, or 2^-n seconds when the
+    // most significant bit of if_tsresol is set.
+    pub fn timestamp(&self, units: u64) -> Duration {
+        let exponent = u32::from(self.ts_resolution & 0x7F);
+        let nanos = if self.ts_resolution & 0x80 == 0 {
+            match 10u128.checked_pow(exponent) {
+                Some(per_second) => u128::from(units) * 1_000_000_000 / per_second,
+                None => 0
+            }
+        } else {
+            (u128::from(units) * 1_000_000_000) >> exponent.min(127)
+        };
+        let offset = Duration::from_secs(self.ts_offset.unsigned_abs());
+        let timestamp = Duration::from_nanos(nanos.min(u64::MAX as u128) as u64);
+
+        if self.ts_offset >= 0 {
+            timestamp + offset
+        } else {
+            timestamp.saturating_sub(offset)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EnhancedPacket {
+    pub interface_id: u32,
+    pub timestamp: u64,
+    pub captured_len: u32,
+    pub original_len: u32,
+    pub data: Vec<u8>,
+    pub flags: Option<u32>,
+    pub drop_count: Option<u64>,
+    pub comments: Vec<String>
+}
+
+impl EnhancedPacket {
+    const SIZE: usize = 20;
+
+    fn new(body: &[u8], byte_order: ByteOrder) -> Result<EnhancedPacket, PcapNgError> {
+        let bytes = slice(body, 0, Self::SIZE)?;
+        let captured_len = byte_order.u32(&bytes[12..16]);
+        let data_len = captured_len as usize;
+        let data = slice(body, Self::SIZE, data_len)?;
+
+        let mut packet = EnhancedPacket {
+            interface_id: byte_order.u32(&bytes[0..4]),
+            timestamp: u64::from(byte_order.u32(&bytes[4..8])) << 32 | u64::from(byte_order.u32(&bytes[8..12])),
+            captured_len,
+            original_len: byte_order.u32(&bytes[16..20]),
+            data: data.to_vec(),
+            flags: None,
+            drop_count: None,
+            comments: vec![]
+        };
+
+        let options_start = (Self::SIZE + padded(data_len)).min(body.len());
+        for (code, value) in options(&body[options_start..], byte_order)? {
+            match code {
+                OPT_COMMENT => packet.comments.push(string(value)),
+                2 if value.len() >= 4 => packet.flags = Some(byte_order.u32(value)),
+                4 if value.len() >= 8 => packet.drop_count = Some(byte_order.u64(value)),
+                _ => {}
+            }
+        }
+        Ok(packet)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SimplePacket {
+    pub original_len: u32,
+    pub data: Vec<u8>
+}
+
+impl SimplePacket {
+    const SIZE: usize = 4;
+
+    fn new(body: &[u8], byte_order: ByteOrder) -> Result<SimplePacket, PcapNgError> {
+        let original_len = byte_order.u32(slice(body, 0, Self::SIZE)?);
+        let data_len = (original_len as usize).min(body.len() - Self::SIZE);
+
+        Ok(SimplePacket {
+            original_len,
+            data: body[Self::SIZE..Self::SIZE + data_len].to_vec()
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NameRecord {
+    pub addr: IpAddr,
+    pub names: Vec<String>
+}
+
+impl NameRecord {
+    fn list(body: &[u8], byte_order: ByteOrder) -> Result<Vec<NameRecord>, PcapNgError> {
+        let mut records = vec![];
+        for (record_type, value) in options(body, byte_order)? {
+            let (addr, names) = match record_type {
+                1 if value.len() >= 4 => {
+                    let addr: [u8; 4] = value[..4].try_into()?;
+                    (IpAddr::V4(Ipv4Addr::from(addr)), &value[4..])
+                }
+                2 if value.len() >= 16 => {
+                    let addr: [u8; 16] = value[..16].try_into()?;
+                    (IpAddr::V6(Ipv6Addr::from(addr)), &value[16..])
+                }
+                _ => continue
+            };
+
+            records.push(NameRecord {
+                addr,
+                names: names.split(|b| *b == 0).filter(|name| !name.is_empty()).map(string).collect()
+            });
+        }
+        Ok(records)
+    }
+}
+
+// Options and name resolution records share the same TLV layout: a 16-bit code,
+// a 16-bit length and a value padded to 32 bits.
+fn options(mut bytes: &[u8], byte_order: ByteOrder) -> Result<Vec<(u16, &[u8])>, PcapNgError> {
+    let mut res = vec![];
+    while bytes.len() >= 4 {
+        let code = byte_order.u16(&bytes[0..2]);
+        let len = byte_order.u16(&bytes[2..4]) as usize;
+        if code == OPT_END {
+            break;
+        }
+
+        res.push((code, slice(bytes, 4, len)?));
+        bytes = &bytes[(4 + padded(len)).min(bytes.len())..];
+    }
+    Ok(res)
+}
+
+fn slice(bytes: &[u8], start: usize, len: usize) -> Result<&[u8], PcapNgError> {
+    bytes.get(start..start + len).ok_or(PcapNgError::TruncatedBlock)
+}
+
+pub fn padded(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+fn string(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).trim_end_matches('\0').to_string()
+}
@@ -0,0 +1,98 @@
+use std::io::{Result, Write};
+use std::time::Duration;
+
+use crate::common::pcapng::block::{padded, BYTE_ORDER_MAGIC, ENHANCED_PACKET, INTERFACE_DESCRIPTION, SECTION_HEADER};
+
+const OPT_END: u16 = 0;
+const OPT_COMMENT: u16 = 1;
+const IF_NAME: u16 = 2;
+const IF_TSRESOL: u16 = 9;
+const NANOSECONDS: u8 = 9;
+
+pub struct PcapNgWriter<W: Write> {
+    writer: W,
+    interfaces: u32
+}
+
+impl<W: Write> PcapNgWriter<W> {
+    pub fn new(mut writer: W) -> Result<PcapNgWriter<W>> {
+        let mut body = vec![];
+        body.extend(BYTE_ORDER_MAGIC.to_le_bytes());
+        body.extend(1u16.to_le_bytes());
+        body.extend(0u16.to_le_bytes());
+        body.extend((-1i64).to_le_bytes());
+        push_option(&mut body, 4, env!("CARGO_PKG_NAME").as_bytes());
+        push_option(&mut body, OPT_END, &[]);
+        write_block(&mut writer, SECTION_HEADER, &body)?;
+
+        Ok(PcapNgWriter {
+            writer,
+            interfaces: 0
+        })
+    }
+
+    pub fn add_interface(&mut self, link_type: u16, snap_len: u32, name: Option<&str>) -> Result<u32> {
+        let mut body = vec![];
+        body.extend(link_type.to_le_bytes());
+        body.extend(0u16.to_le_bytes());
+        body.extend(snap_len.to_le_bytes());
+        if let Some(name) = name {
+            push_option(&mut body, IF_NAME, name.as_bytes());
+        }
+        push_option(&mut body, IF_TSRESOL, &[NANOSECONDS]);
+        push_option(&mut body, OPT_END, &[]);
+        write_block(&mut self.writer, INTERFACE_DESCRIPTION, &body)?;
+
+        self.interfaces += 1;
+        Ok(self.interfaces - 1)
+    }
+
+    pub fn interfaces(&self) -> u32 {
+        self.interfaces
+    }
+
+    pub fn write_packet(&mut self, interface_id: u32, timestamp: Duration, original_len: u32, data: &[u8], comment: Option<&str>) -> Result<()> {
+        let nanos = timestamp.as_nanos() as u64;
+
+        let mut body = Vec::with_capacity(32 + padded(data.len()));
+        body.extend(interface_id.to_le_bytes());
+        body.extend(((nanos >> 32) as u32).to_le_bytes());
+        body.extend((nanos as u32).to_le_bytes());
+        body.extend((data.len() as u32).to_le_bytes());
+        body.extend(original_len.to_le_bytes());
+        body.extend(data);
+        body.resize(body.len() + padded(data.len()) - data.len(), 0);
+        if let Some(comment) = comment {
+            push_option(&mut body, OPT_COMMENT, comment.as_bytes());
+            push_option(&mut body, OPT_END, &[]);
+        }
+        write_block(&mut self.writer, ENHANCED_PACKET, &body)
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+fn push_option(body: &mut Vec<u8>, code: u16, value: &[u8]) {
+    body.extend(code.to_le_bytes());
+    body.extend((value.len() as u16).to_le_bytes());
+    body.extend(value);
+    body.resize(body.len() + padded(value.len()) - value.len(), 0);
+}
+
+fn write_block<W: Write>(writer: &mut W, block_type: u32, body: &[u8]) -> Result<()> {
+    let total_length = (body.len() as u32 + 12).to_le_bytes();
+    writer.write_all(&block_type.to_le_bytes())?;
+    writer.write_all(&total_length)?;
+    writer.write_all(body)?;
+    writer.write_all(&total_length)
+}
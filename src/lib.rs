@@ -10,6 +10,10 @@ pub mod pcap {
     pub use crate::common::pcap::*;
 }
 
+pub mod pcapng {
+    pub use crate::common::pcapng::*;
+}
+
 pub mod app {
     pub use crate::common::app::*;
 }
\ No newline at end of file